@@ -0,0 +1,265 @@
+use std::fmt;
+use std::ops::Range;
+
+use Operation;
+use Program;
+
+// the control-flow graph of a program: basic blocks are runs of instructions
+// always executed together, edges are the ways to go from one to another.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EdgeKind {
+    // the next instruction, after anything but a jump
+    FallThrough,
+    Jump,
+    // the two ways out of jez and jneg
+    Taken,
+    NotTaken
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Edge {
+    pub kind: EdgeKind,
+    // None when the program goes past its last instruction
+    pub to: Option<usize>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BasicBlock {
+    // instructions from start to end, end excluded
+    pub start: usize,
+    pub end: usize,
+    // the labels found in the block, in order
+    pub labels: Vec<String>,
+    pub successors: Vec<Edge>
+}
+
+impl BasicBlock {
+    pub fn instructions(&self) -> Range<usize> {
+        self.start..self.end
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControlFlowGraph {
+    // in program order, the first one being the entry
+    pub blocks: Vec<BasicBlock>
+}
+
+// where the program can go after an instruction
+fn jump_target(operation: Operation) -> Option<usize> {
+    match operation {
+        Operation::Jump{next_operation} |
+        Operation::JumpEqualsZero{next_operation} |
+        Operation::JumpNegative{next_operation} => Some(next_operation),
+        _ => None
+    }
+}
+
+impl ControlFlowGraph {
+    pub fn from_program(program: &Program) -> Self {
+        ControlFlowGraph::new(&program.operations, &program.labels)
+    }
+
+    // labels are the names of the hrm-compiler source, with their positions
+    pub fn new(operations: &[Operation], labels: &[(String, usize)]) -> Self {
+        // blocks start at the entry, at jump targets and right after jumps
+        let mut leaders = vec!(false; operations.len());
+        for (position, operation) in operations.iter().enumerate() {
+            if let Some(target) = jump_target(*operation) {
+                if let Some(leader) = leaders.get_mut(target) {
+                    *leader = true;
+                }
+                if let Some(leader) = leaders.get_mut(position + 1) {
+                    *leader = true;
+                }
+            }
+        }
+        if let Some(leader) = leaders.first_mut() {
+            *leader = true;
+        }
+
+        let starts: Vec<usize> = (0..operations.len()).filter(|&position| leaders[position]).collect();
+        let block_at = |instruction: usize| starts.binary_search(&instruction).ok();
+
+        let blocks = starts.iter().enumerate()
+            .map(|(index, &start)| {
+                let end = starts.get(index + 1).cloned().unwrap_or(operations.len());
+                let last = operations[end - 1];
+                let fall_through = |kind| Edge{kind, to: block_at(end)};
+                let successors = match (last, jump_target(last)) {
+                    (Operation::Jump{..}, Some(target)) => vec!(Edge{kind: EdgeKind::Jump, to: block_at(target)}),
+                    (_, Some(target)) => vec!(Edge{kind: EdgeKind::Taken, to: block_at(target)}, fall_through(EdgeKind::NotTaken)),
+                    _ => vec!(fall_through(EdgeKind::FallThrough))
+                };
+
+                let mut block_labels: Vec<&(String, usize)> = labels.iter()
+                    .filter(|&&(_, position)| position >= start && position < end)
+                    .collect();
+                block_labels.sort_by_key(|&&(_, position)| position);
+
+                BasicBlock {
+                    start,
+                    end,
+                    labels: block_labels.into_iter().map(|label| label.0.clone()).collect(),
+                    successors
+                }
+            })
+            .collect();
+
+        ControlFlowGraph{blocks}
+    }
+
+    // the block holding an instruction
+    pub fn block_of(&self, instruction: usize) -> Option<usize> {
+        self.blocks.iter().position(|block| block.start <= instruction && instruction < block.end)
+    }
+
+    pub fn predecessors(&self, block: usize) -> Vec<usize> {
+        (0..self.blocks.len())
+            .filter(|&index| self.blocks[index].successors.iter().any(|edge| edge.to == Some(block)))
+            .collect()
+    }
+
+    // what holds entering each block, following every path from the entry until
+    // nothing changes; None for the blocks no path gets to. transfer runs a block.
+    pub fn forward<S, J, T>(&self, start: S, join: J, mut transfer: T) -> Vec<Option<S>>
+            where S: Clone + PartialEq, J: Fn(&S, &S) -> S, T: FnMut(&BasicBlock, &mut S) {
+        let mut entering: Vec<Option<S>> = vec!(None; self.blocks.len());
+        if let Some(first) = entering.first_mut() {
+            *first = Some(start);
+        }
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (index, block) in self.blocks.iter().enumerate() {
+                let mut leaving = match entering[index] {
+                    Some(ref entering) => entering.clone(),
+                    None => continue
+                };
+                transfer(block, &mut leaving);
+                for successor in block.successors.iter().filter_map(|edge| edge.to) {
+                    let joined = match entering[successor] {
+                        Some(ref other) => join(other, &leaving),
+                        None => leaving.clone()
+                    };
+                    if entering[successor].as_ref() != Some(&joined) {
+                        entering[successor] = Some(joined);
+                        changed = true;
+                    }
+                }
+            }
+        }
+        entering
+    }
+
+    // the blocks some path from the entry goes through
+    pub fn reachable(&self) -> Vec<bool> {
+        let mut reachable = vec!(false; self.blocks.len());
+        let mut pending = if self.blocks.is_empty() { vec!() } else { vec!(0) };
+        while let Some(block) = pending.pop() {
+            if reachable[block] {
+                continue;
+            }
+            reachable[block] = true;
+            pending.extend(self.blocks[block].successors.iter().filter_map(|edge| edge.to));
+        }
+        reachable
+    }
+}
+
+impl fmt::Display for EdgeKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EdgeKind::FallThrough => write!(f, "falls through"),
+            EdgeKind::Jump => write!(f, "jumps"),
+            EdgeKind::Taken => write!(f, "taken"),
+            EdgeKind::NotTaken => write!(f, "not taken")
+        }
+    }
+}
+
+// one block per line: "block 1 (3..5, loop): taken -> block 0, not taken -> end"
+impl fmt::Display for ControlFlowGraph {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, block) in self.blocks.iter().enumerate() {
+            write!(f, "block {} ({}..{}", index, block.start, block.end)?;
+            for label in &block.labels {
+                write!(f, ", {}", label)?;
+            }
+            let successors: Vec<String> = block.successors.iter()
+                .map(|edge| match edge.to {
+                    Some(to) => format!("{} -> block {}", edge.kind, to),
+                    None => format!("{} -> end", edge.kind)
+                })
+                .collect();
+            writeln!(f, "): {}", successors.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use Operation;
+    use Location;
+    use analysis::cfg::{ControlFlowGraph, Edge, EdgeKind};
+
+    // outputs the positive items, stopping at the first zero
+    fn program() -> (Vec<Operation>, Vec<(String, usize)>) {
+        let operations = vec!(
+            Operation::Label,
+            Operation::Inbox,
+            Operation::JumpEqualsZero{next_operation: 7},
+            Operation::JumpNegative{next_operation: 0},
+            Operation::CopyTo{cell: Location::Cell(0)},
+            Operation::Outbox,
+            Operation::Jump{next_operation: 0},
+            Operation::Label
+        );
+        (operations, vec!((String::from("start"), 0), (String::from("done"), 7)))
+    }
+
+    #[test]
+    fn splits_at_jumps_and_targets() {
+        let (operations, labels) = program();
+        let graph = ControlFlowGraph::new(&operations, &labels);
+
+        let ranges: Vec<_> = graph.blocks.iter().map(|block| (block.start, block.end)).collect();
+        assert_eq!(ranges, vec!((0, 3), (3, 4), (4, 7), (7, 8)));
+        assert_eq!(graph.blocks[0].labels, vec!(String::from("start")));
+        assert_eq!(graph.blocks[3].labels, vec!(String::from("done")));
+        assert_eq!(graph.blocks[0].successors,
+            vec!(Edge{kind: EdgeKind::Taken, to: Some(3)}, Edge{kind: EdgeKind::NotTaken, to: Some(1)}));
+        assert_eq!(graph.blocks[2].successors, vec!(Edge{kind: EdgeKind::Jump, to: Some(0)}));
+        assert_eq!(graph.blocks[3].successors, vec!(Edge{kind: EdgeKind::FallThrough, to: None}));
+        assert_eq!(graph.block_of(5), Some(2));
+        assert_eq!(graph.predecessors(0), vec!(1, 2));
+    }
+
+    #[test]
+    fn display() {
+        let (operations, labels) = program();
+
+        assert_eq!(ControlFlowGraph::new(&operations, &labels).to_string(), "\
+block 0 (0..3, start): taken -> block 3, not taken -> block 1
+block 1 (3..4): taken -> block 0, not taken -> block 2
+block 2 (4..7): jumps -> block 0
+block 3 (7..8, done): falls through -> end
+");
+    }
+
+    #[test]
+    fn reachable_blocks() {
+        let operations = vec!(
+            Operation::Inbox,
+            Operation::Jump{next_operation: 3},
+            Operation::Outbox,
+            Operation::Outbox
+        );
+
+        assert_eq!(ControlFlowGraph::new(&operations, &[]).reachable(), vec!(true, false, true));
+        assert!(ControlFlowGraph::new(&[], &[]).reachable().is_empty());
+    }
+}
@@ -0,0 +1,214 @@
+use std::fmt;
+
+use Operation;
+use Location;
+use debug::Subject;
+use state::InternalState;
+use analysis::cfg::ControlFlowGraph;
+use analysis::{location_of, floor_length};
+
+// reads of the register or of a tile that can be empty when the program gets
+// there, found by following every path from the start with what the floor holds.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filling {
+    Full,
+    Empty,
+    // full on some paths, empty on others
+    Maybe
+}
+
+impl Filling {
+    fn join(self, other: Filling) -> Filling {
+        if self == other { self } else { Filling::Maybe }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Hands {
+    register: Filling,
+    cells: Vec<Filling>
+}
+
+impl Hands {
+    fn join(&self, other: &Hands) -> Hands {
+        Hands {
+            register: self.register.join(other.register),
+            cells: self.cells.iter().zip(&other.cells).map(|(cell, other)| cell.join(*other)).collect()
+        }
+    }
+
+    fn get(&self, subject: Subject) -> Filling {
+        match subject {
+            Subject::Register => self.register,
+            Subject::Cell(cell) => self.cells[cell]
+        }
+    }
+
+    fn fill(&mut self, subject: Subject) {
+        match subject {
+            Subject::Register => self.register = Filling::Full,
+            Subject::Cell(cell) => self.cells[cell] = Filling::Full
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EmptyRead {
+    pub instruction: usize,
+    pub operation: Operation,
+    pub subject: Subject,
+    // Empty when the read fails on every path, Maybe when only on some
+    pub filling: Filling
+}
+
+// the subjects read, and written, by an operation; tiles behind pointers are unknown
+fn reads_and_writes(operation: Operation) -> (Vec<Subject>, Vec<Subject>) {
+    let (pointer, tile) = match location_of(operation) {
+        Some(Location::Cell(cell)) => (None, Some(Subject::Cell(cell))),
+        Some(Location::Address(pointer)) => (Some(Subject::Cell(pointer)), None),
+        None => (None, None)
+    };
+    let register = Some(Subject::Register);
+
+    let (reads, writes) = match operation {
+        Operation::Inbox => (vec!(), vec!(register)),
+        Operation::Outbox | Operation::JumpEqualsZero{..} | Operation::JumpNegative{..} => (vec!(register), vec!()),
+        Operation::CopyFrom{..} => (vec!(pointer, tile), vec!(register)),
+        Operation::CopyTo{..} => (vec!(pointer, register), vec!(tile)),
+        Operation::Add{..} | Operation::Sub{..} => (vec!(pointer, register, tile), vec!(register)),
+        Operation::BumpPlus{..} | Operation::BumpMinus{..} => (vec!(pointer, tile), vec!(register)),
+        Operation::Label | Operation::Jump{..} => (vec!(), vec!())
+    };
+    (reads.into_iter().flatten().collect(), writes.into_iter().flatten().collect())
+}
+
+// runs one instruction on the hands, reporting its reads of empty things
+fn transfer(instruction: usize, operation: Operation, hands: &mut Hands, found: &mut Vec<EmptyRead>) {
+    let (reads, writes) = reads_and_writes(operation);
+    for subject in reads {
+        let filling = hands.get(subject);
+        if filling != Filling::Full {
+            found.push(EmptyRead{instruction, operation, subject, filling});
+        }
+        // the program only goes on if the read worked
+        hands.fill(subject);
+    }
+    for subject in writes {
+        hands.fill(subject);
+    }
+    // writing through a pointer may fill any tile
+    if let Operation::CopyTo{cell: Location::Address(_)} = operation {
+        for cell in hands.cells.iter_mut() {
+            *cell = cell.join(Filling::Full);
+        }
+    }
+}
+
+// initial gives the register and the floor the program starts with
+pub fn empty_reads(operations: &[Operation], initial: &InternalState) -> Vec<EmptyRead> {
+    let graph = ControlFlowGraph::new(operations, &[]);
+    let filling = |value: Option<_>| if value.is_some() { Filling::Full } else { Filling::Empty };
+    let start = Hands {
+        register: filling(initial.register),
+        cells: (0..floor_length(operations, initial))
+            .map(|cell| filling(initial.memory.get(cell).cloned().unwrap_or(None)))
+            .collect()
+    };
+
+    let entering = graph.forward(start, Hands::join, |block, hands| {
+        for instruction in block.instructions() {
+            transfer(instruction, operations[instruction], hands, &mut vec!());
+        }
+    });
+
+    let mut found = vec!();
+    for (block, hands) in graph.blocks.iter().zip(entering) {
+        if let Some(mut hands) = hands {
+            for instruction in block.instructions() {
+                transfer(instruction, operations[instruction], &mut hands, &mut found);
+            }
+        }
+    }
+    found
+}
+
+impl fmt::Display for EmptyRead {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let subject = match self.subject {
+            Subject::Register => String::from("the register"),
+            Subject::Cell(cell) => format!("cell {}", cell)
+        };
+        let how = if self.filling == Filling::Empty { "is always" } else { "may be" };
+        write!(f, "instruction {} ({:?}): {} {} empty", self.instruction, self.operation, subject, how)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use Value;
+    use Location;
+    use Operation;
+    use debug::Subject;
+    use state::InternalState;
+    use analysis::initialization::{EmptyRead, Filling, empty_reads};
+
+    fn subjects(found: Vec<EmptyRead>) -> Vec<(usize, Subject, Filling)> {
+        found.into_iter().map(|read| (read.instruction, read.subject, read.filling)).collect()
+    }
+
+    #[test]
+    fn empty_register_and_cells() {
+        let operations = vec!(
+            Operation::Outbox,
+            Operation::CopyFrom{cell: Location::Cell(0)},
+            Operation::Add{cell: Location::Cell(1)},
+            Operation::CopyTo{cell: Location::Cell(2)},
+            Operation::Sub{cell: Location::Cell(2)},
+            Operation::CopyFrom{cell: Location::Address(3)}
+        );
+        let initial = InternalState::new(None, 0).with_memory(vec!(None, Some(Value::Number{value: 1}), None));
+
+        assert_eq!(subjects(empty_reads(&operations, &initial)), vec!(
+            (0, Subject::Register, Filling::Empty),
+            (1, Subject::Cell(0), Filling::Empty),
+            (5, Subject::Cell(3), Filling::Empty)
+        ));
+    }
+
+    #[test]
+    fn empty_on_some_paths() {
+        // the first item skips the copy to the floor when it is zero
+        let operations = vec!(
+            Operation::Inbox,
+            Operation::JumpEqualsZero{next_operation: 3},
+            Operation::CopyTo{cell: Location::Cell(0)},
+            Operation::Label,
+            Operation::CopyFrom{cell: Location::Cell(0)},
+            Operation::Outbox
+        );
+        let found = empty_reads(&operations, &InternalState::new(None, 0));
+
+        assert_eq!(subjects(found.clone()), vec!((4, Subject::Cell(0), Filling::Maybe)));
+        assert_eq!(found[0].to_string(), "instruction 4 (CopyFrom { cell: Cell(0) }): cell 0 may be empty");
+    }
+
+    #[test]
+    fn loops_and_pointers() {
+        // the loop fills the cell before reading it on every turn
+        let operations = vec!(
+            Operation::Label,
+            Operation::Inbox,
+            Operation::CopyTo{cell: Location::Cell(1)},
+            Operation::CopyFrom{cell: Location::Address(0)},
+            Operation::Add{cell: Location::Cell(1)},
+            Operation::Outbox,
+            Operation::Jump{next_operation: 0},
+            // never reached
+            Operation::CopyFrom{cell: Location::Cell(2)}
+        );
+        let initial = InternalState::new(None, 0).with_memory(vec!(Some(Value::Number{value: 1}), None, None));
+
+        assert_eq!(empty_reads(&operations, &initial), vec!());
+    }
+}
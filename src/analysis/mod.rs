@@ -0,0 +1,32 @@
+// static analyses of loaded programs: they look at the operations
+// without running them.
+
+pub mod cfg;
+pub mod initialization;
+pub mod reachability;
+pub mod types;
+
+use Operation;
+use Location;
+use state::InternalState;
+
+// the tile an operation works on, directly or through a pointer
+pub fn location_of(operation: Operation) -> Option<Location> {
+    match operation {
+        Operation::Add{cell} | Operation::Sub{cell} | Operation::CopyFrom{cell} |
+        Operation::CopyTo{cell} | Operation::BumpPlus{cell} | Operation::BumpMinus{cell} => Some(cell),
+        _ => None
+    }
+}
+
+// the tiles the analyses follow: the initial memory, and every tile the program names
+pub fn floor_length(operations: &[Operation], initial: &InternalState) -> usize {
+    operations.iter()
+        .filter_map(|operation| match location_of(*operation) {
+            Some(Location::Cell(cell)) | Some(Location::Address(cell)) => Some(cell + 1),
+            None => None
+        })
+        .chain(Some(initial.memory.len()))
+        .max()
+        .unwrap_or(0)
+}
@@ -0,0 +1,141 @@
+use std::fmt;
+
+use Operation;
+use Program;
+use analysis::cfg::ControlFlowGraph;
+
+// code no run of the program can get to, and labels nothing jumps to:
+// what hrm-compiler's dead code removal should have taken away.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeadCode {
+    // instructions no path from the first one reaches, in order
+    pub unreachable: Vec<usize>,
+    // labels no reachable jump goes to, with their positions
+    pub unused_labels: Vec<(String, usize)>
+}
+
+impl DeadCode {
+    pub fn is_empty(&self) -> bool {
+        self.unreachable.is_empty() && self.unused_labels.is_empty()
+    }
+}
+
+pub fn unreachable_instructions(graph: &ControlFlowGraph) -> Vec<usize> {
+    graph.blocks.iter()
+        .zip(graph.reachable())
+        .filter(|&(_, reachable)| !reachable)
+        .flat_map(|(block, _)| block.instructions())
+        .collect()
+}
+
+// jumps in unreachable code do not count: removing that code leaves the label unused
+pub fn unused_labels(operations: &[Operation], labels: &[(String, usize)], unreachable: &[usize]) -> Vec<(String, usize)> {
+    let targets: Vec<usize> = operations.iter().enumerate()
+        .filter(|&(position, _)| !unreachable.contains(&position))
+        .filter_map(|(_, operation)| match *operation {
+            Operation::Jump{next_operation} |
+            Operation::JumpEqualsZero{next_operation} |
+            Operation::JumpNegative{next_operation} => Some(next_operation),
+            _ => None
+        })
+        .collect();
+
+    let mut unused: Vec<(String, usize)> = labels.iter()
+        .filter(|&&(_, position)| !targets.contains(&position))
+        .cloned()
+        .collect();
+    unused.sort_by_key(|label| label.1);
+    unused
+}
+
+pub fn dead_code(program: &Program) -> DeadCode {
+    let graph = ControlFlowGraph::from_program(program);
+    let unreachable = unreachable_instructions(&graph);
+    let unused_labels = unused_labels(&program.operations, &program.labels, &unreachable);
+
+    DeadCode{unreachable, unused_labels}
+}
+
+// consecutive instructions are grouped: "instructions 4 to 6 are never reached"
+impl fmt::Display for DeadCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut runs: Vec<(usize, usize)> = vec!();
+        for &instruction in &self.unreachable {
+            match runs.last_mut() {
+                Some(&mut (_, ref mut last)) if *last + 1 == instruction => *last = instruction,
+                _ => runs.push((instruction, instruction))
+            }
+        }
+
+        for (first, last) in runs {
+            if first == last {
+                writeln!(f, "instruction {} is never reached", first)?;
+            }
+            else {
+                writeln!(f, "instructions {} to {} are never reached", first, last)?;
+            }
+        }
+        for &(ref name, position) in &self.unused_labels {
+            writeln!(f, "label {} (instruction {}) is never jumped to", name, position)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use Operation;
+    use Program;
+    use analysis::reachability::{DeadCode, dead_code};
+
+    fn program(operations: Vec<Operation>, labels: Vec<(&str, usize)>) -> Program {
+        Program {
+            operations,
+            labels: labels.into_iter().map(|(name, position)| (String::from(name), position)).collect()
+        }
+    }
+
+    #[test]
+    fn finds_dead_code() {
+        let found = dead_code(&program(vec!(
+            Operation::Label,
+            Operation::Inbox,
+            Operation::JumpEqualsZero{next_operation: 7},
+            Operation::Outbox,
+            Operation::Jump{next_operation: 0},
+            // nothing goes here: the jump back to the start is dead too
+            Operation::Label,
+            Operation::Jump{next_operation: 0},
+            Operation::Label
+        ), vec!(("start", 0), ("dead", 5), ("end", 7))));
+
+        assert_eq!(found, DeadCode{unreachable: vec!(5, 6), unused_labels: vec!((String::from("dead"), 5))});
+        assert_eq!(found.to_string(), "instructions 5 to 6 are never reached\n\
+            label dead (instruction 5) is never jumped to\n");
+    }
+
+    #[test]
+    fn jumps_from_dead_code_do_not_count() {
+        let found = dead_code(&program(vec!(
+            Operation::Inbox,
+            Operation::Jump{next_operation: 4},
+            Operation::Label,
+            Operation::Jump{next_operation: 2},
+            Operation::Outbox
+        ), vec!(("loop", 2))));
+
+        assert_eq!(found.unreachable, vec!(2, 3));
+        assert_eq!(found.unused_labels, vec!((String::from("loop"), 2)));
+        assert_eq!(found.to_string(), "instructions 2 to 3 are never reached\n\
+            label loop (instruction 2) is never jumped to\n");
+    }
+
+    #[test]
+    fn nothing_dead() {
+        let found = dead_code(&program(vec!(Operation::Label, Operation::Inbox, Operation::Outbox, Operation::Jump{next_operation: 0}), vec!(("a", 0))));
+
+        assert!(found.is_empty());
+        assert_eq!(found.to_string(), "");
+    }
+}
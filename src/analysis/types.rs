@@ -0,0 +1,372 @@
+use std::fmt;
+
+use Value;
+use Operation;
+use Location;
+use state::{InternalState, Semantics};
+use analysis::cfg::ControlFlowGraph;
+use analysis::floor_length;
+
+// what kind of value the register and each tile can hold at every instruction,
+// whatever the inbox gives, to find the instructions that can fail on a letter.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Number,
+    Letter
+}
+
+// the kinds a value may have; all of them is unknown, none is no value at all
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Kinds {
+    pub number: bool,
+    pub letter: bool,
+    pub empty: bool
+}
+
+const NOTHING: Kinds = Kinds{number: false, letter: false, empty: false};
+const NUMBER: Kinds = Kinds{number: true, letter: false, empty: false};
+const EMPTY: Kinds = Kinds{number: false, letter: false, empty: true};
+// what the inbox gives
+const ANY_ITEM: Kinds = Kinds{number: true, letter: true, empty: false};
+
+impl Kinds {
+    fn of(value: Option<Value>) -> Kinds {
+        match value {
+            Some(Value::Number{..}) => NUMBER,
+            Some(Value::Character{..}) => Kinds{letter: true, ..NOTHING},
+            None => EMPTY
+        }
+    }
+
+    fn of_kind(kind: Kind) -> Kinds {
+        match kind {
+            Kind::Number => NUMBER,
+            Kind::Letter => Kinds{letter: true, ..NOTHING}
+        }
+    }
+
+    pub fn join(&self, other: &Kinds) -> Kinds {
+        Kinds{number: self.number || other.number, letter: self.letter || other.letter, empty: self.empty || other.empty}
+    }
+
+    // the kinds of the values actually there: reading an empty tile stops the program
+    fn values(&self) -> Vec<Kind> {
+        let mut kinds = vec!();
+        if self.number {
+            kinds.push(Kind::Number);
+        }
+        if self.letter {
+            kinds.push(Kind::Letter);
+        }
+        kinds
+    }
+
+    fn filled(&self) -> Kinds {
+        Kinds{empty: false, ..*self}
+    }
+
+    fn without_letters(&self) -> Kinds {
+        Kinds{letter: false, empty: false, ..*self}
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Floor {
+    register: Kinds,
+    cells: Vec<Kinds>
+}
+
+impl Floor {
+    fn join(&self, other: &Floor) -> Floor {
+        Floor {
+            register: self.register.join(&other.register),
+            cells: self.cells.iter().zip(&other.cells).map(|(cell, other)| cell.join(other)).collect()
+        }
+    }
+
+    // what any tile may hold, for tiles behind pointers
+    fn any_cell(&self) -> Kinds {
+        self.cells.iter().fold(NOTHING, |kinds, cell| kinds.join(cell))
+    }
+}
+
+// None once every path has stopped on an error
+fn join(floor: &Option<Floor>, other: &Option<Floor>) -> Option<Floor> {
+    match (floor, other) {
+        (Some(floor), Some(other)) => Some(floor.join(other)),
+        (Some(floor), None) | (None, Some(floor)) => Some(floor.clone()),
+        (None, None) => None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Misuse {
+    // Location::Address when the pointer may lead to any tile
+    BumpedLetter{cell: Location},
+    LetterComparedToZero,
+    LetterPointer{cell: usize},
+    // the register first, then the operand
+    LetterAddition{register: Kind, operand: Kind},
+    LetterSubtraction{register: Kind, operand: Kind}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TypeProblem {
+    pub instruction: usize,
+    pub operation: Operation,
+    pub misuse: Misuse,
+    // whether the instruction fails whatever the inbox, or only on some inboxes
+    pub always: bool
+}
+
+// the kind of the result of an addition or subtraction, like the operators compute it
+fn arithmetic(operation: Operation, register: Kind, operand: Kind, semantics: Semantics) -> Result<Kind, Misuse> {
+    let extended = semantics == Semantics::Extended;
+    match (operation, register, operand) {
+        (_, Kind::Number, Kind::Number) => Ok(Kind::Number),
+        (Operation::Add{..}, Kind::Letter, Kind::Number) | (Operation::Add{..}, Kind::Number, Kind::Letter) if extended => Ok(Kind::Letter),
+        (Operation::Add{..}, _, _) => Err(Misuse::LetterAddition{register, operand}),
+        (_, Kind::Letter, Kind::Letter) => Ok(Kind::Number),
+        (_, Kind::Number, Kind::Letter) if extended => Ok(Kind::Letter),
+        _ => Err(Misuse::LetterSubtraction{register, operand})
+    }
+}
+
+struct Interpreter<'p> {
+    operations: &'p [Operation],
+    semantics: Semantics
+}
+
+impl<'p> Interpreter<'p> {
+    // the tile of the operation and what it may hold, checking the pointer first
+    fn operand(&self, instruction: usize, location: Location, floor: &mut Floor, found: &mut Vec<TypeProblem>) -> (Option<usize>, Kinds) {
+        match location {
+            Location::Cell(cell) => (Some(cell), floor.cells[cell]),
+            Location::Address(pointer) => {
+                let kinds = floor.cells[pointer];
+                if kinds.letter {
+                    found.push(TypeProblem {
+                        instruction,
+                        operation: self.operations[instruction],
+                        misuse: Misuse::LetterPointer{cell: pointer},
+                        always: !kinds.number
+                    });
+                }
+                floor.cells[pointer] = kinds.without_letters();
+                // a pointer that is always a letter gets to no tile
+                (None, if kinds.number { floor.any_cell() } else { NOTHING })
+            }
+        }
+    }
+
+    // runs the instruction on the kinds; the program only goes on if it worked
+    fn transfer(&self, instruction: usize, floor: &mut Floor, found: &mut Vec<TypeProblem>) {
+        let operation = self.operations[instruction];
+        let mut report = |misuse, always| found.push(TypeProblem{instruction, operation, misuse, always});
+
+        match operation {
+            Operation::Inbox => floor.register = ANY_ITEM,
+            Operation::Outbox | Operation::Label | Operation::Jump{..} => (),
+            Operation::JumpEqualsZero{..} => floor.register = floor.register.filled(),
+            Operation::JumpNegative{..} => {
+                if floor.register.letter {
+                    report(Misuse::LetterComparedToZero, !floor.register.number);
+                }
+                floor.register = floor.register.without_letters();
+            },
+            Operation::CopyFrom{cell} => {
+                let (_, kinds) = self.operand(instruction, cell, floor, found);
+                floor.register = kinds.filled();
+            },
+            Operation::CopyTo{cell} => {
+                let (tile, _) = self.operand(instruction, cell, floor, found);
+                let register = floor.register.filled();
+                match tile {
+                    Some(tile) => floor.cells[tile] = register,
+                    // any tile may get the value
+                    None => for kinds in floor.cells.iter_mut() {
+                        *kinds = kinds.join(&register);
+                    }
+                }
+                floor.register = register;
+            },
+            Operation::BumpPlus{cell} | Operation::BumpMinus{cell} => {
+                // behind a pointer, kinds is what any tile may hold
+                let (tile, kinds) = self.operand(instruction, cell, floor, found);
+                if kinds.letter {
+                    found.push(TypeProblem{instruction, operation, misuse: Misuse::BumpedLetter{cell}, always: !kinds.number});
+                }
+                if let Some(tile) = tile {
+                    floor.cells[tile] = kinds.without_letters();
+                }
+                floor.register = NUMBER;
+            },
+            Operation::Add{cell} | Operation::Sub{cell} => {
+                let (_, kinds) = self.operand(instruction, cell, floor, found);
+                let mut result = NOTHING;
+                let mut misuses = vec!();
+                for register in floor.register.values() {
+                    for operand in kinds.values() {
+                        match arithmetic(operation, register, operand, self.semantics) {
+                            Ok(kind) => result = result.join(&Kinds::of_kind(kind)),
+                            Err(misuse) => misuses.push(misuse)
+                        }
+                    }
+                }
+                if let Some(&misuse) = misuses.first() {
+                    found.push(TypeProblem{instruction, operation, misuse, always: result == NOTHING});
+                }
+                floor.register = result;
+            }
+        }
+    }
+
+    // the instructions after one that always fails are never run
+    fn run(&self, instruction: usize, state: &mut Option<Floor>, found: &mut Vec<TypeProblem>) {
+        let before = found.len();
+        if let Some(ref mut floor) = *state {
+            self.transfer(instruction, floor, found);
+        }
+        if found[before..].iter().any(|problem| problem.always) {
+            *state = None;
+        }
+    }
+}
+
+// initial gives the floor and the semantics the program starts with
+pub fn type_problems(operations: &[Operation], initial: &InternalState) -> Vec<TypeProblem> {
+    let graph = ControlFlowGraph::new(operations, &[]);
+    let interpreter = Interpreter{operations, semantics: initial.semantics};
+    let start = Floor {
+        register: Kinds::of(initial.register),
+        cells: (0..floor_length(operations, initial))
+            .map(|cell| Kinds::of(initial.memory.get(cell).cloned().unwrap_or(None)))
+            .collect()
+    };
+
+    let entering = graph.forward(Some(start), join, |block, state| {
+        for instruction in block.instructions() {
+            interpreter.run(instruction, state, &mut vec!());
+        }
+    });
+
+    let mut found = vec!();
+    for (block, state) in graph.blocks.iter().zip(entering) {
+        if let Some(mut state) = state {
+            for instruction in block.instructions() {
+                interpreter.run(instruction, &mut state, &mut found);
+            }
+        }
+    }
+    found
+}
+
+fn a_kind(kind: Kind) -> &'static str {
+    match kind {
+        Kind::Number => "a number",
+        Kind::Letter => "a letter"
+    }
+}
+
+impl fmt::Display for Misuse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Misuse::BumpedLetter{cell: Location::Cell(cell)} => write!(f, "bump the letter in cell {}", cell),
+            Misuse::BumpedLetter{cell: Location::Address(pointer)} => write!(f, "bump a letter in the tile cell {} points to", pointer),
+            Misuse::LetterComparedToZero => write!(f, "compare a letter with zero"),
+            Misuse::LetterPointer{cell} => write!(f, "use the letter in cell {} as a pointer", cell),
+            Misuse::LetterAddition{register, operand} => write!(f, "add {} to {}", a_kind(operand), a_kind(register)),
+            Misuse::LetterSubtraction{register, operand} => write!(f, "subtract {} from {}", a_kind(operand), a_kind(register))
+        }
+    }
+}
+
+impl fmt::Display for TypeProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let how = if self.always { "always" } else { "may" };
+        write!(f, "instruction {} ({:?}): {} {}", self.instruction, self.operation, how, self.misuse)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use Value;
+    use Location;
+    use Operation;
+    use state::{InternalState, Semantics};
+    use analysis::types::{Kind, Misuse, TypeProblem, type_problems};
+
+    fn misuses(found: Vec<TypeProblem>) -> Vec<(usize, Misuse, bool)> {
+        found.into_iter().map(|problem| (problem.instruction, problem.misuse, problem.always)).collect()
+    }
+
+    #[test]
+    fn inbox_items_may_be_letters() {
+        let operations = vec!(
+            Operation::Inbox,
+            Operation::CopyTo{cell: Location::Cell(0)},
+            Operation::BumpPlus{cell: Location::Cell(0)},
+            // a number now, since the bump worked
+            Operation::JumpNegative{next_operation: 5},
+            Operation::BumpMinus{cell: Location::Cell(0)},
+            Operation::Label
+        );
+        let found = type_problems(&operations, &InternalState::new(None, 0));
+
+        assert_eq!(misuses(found.clone()), vec!((2, Misuse::BumpedLetter{cell: Location::Cell(0)}, false)));
+        assert_eq!(found[0].to_string(), "instruction 2 (BumpPlus { cell: Cell(0) }): may bump the letter in cell 0");
+    }
+
+    #[test]
+    fn floor_kinds() {
+        let initial = InternalState::new(None, 0)
+            .with_memory(vec!(Some(Value::Character{value: 'a'}), Some(Value::Character{value: 'b'}), Some(Value::Number{value: 1})));
+        let problems = |operations: Vec<Operation>| misuses(type_problems(&operations, &initial));
+
+        assert_eq!(problems(vec!(Operation::CopyFrom{cell: Location::Cell(0)}, Operation::Sub{cell: Location::Cell(2)})),
+            vec!((1, Misuse::LetterSubtraction{register: Kind::Letter, operand: Kind::Number}, true)));
+        assert_eq!(problems(vec!(Operation::CopyFrom{cell: Location::Address(2)}, Operation::Add{cell: Location::Address(1)})),
+            vec!((1, Misuse::LetterPointer{cell: 1}, true)));
+        // nothing runs after an instruction that always fails
+        assert_eq!(problems(vec!(
+            Operation::CopyFrom{cell: Location::Cell(0)},
+            Operation::Add{cell: Location::Cell(1)},
+            Operation::JumpNegative{next_operation: 3},
+            Operation::Label
+        )), vec!((1, Misuse::LetterAddition{register: Kind::Letter, operand: Kind::Letter}, true)));
+    }
+
+    #[test]
+    fn bump_through_a_pointer() {
+        // cell 0 points to cell 2, but the analysis only knows some tile may hold a letter
+        let initial = InternalState::new(None, 0)
+            .with_memory(vec!(Some(Value::Number{value: 2}), Some(Value::Character{value: 'a'}), Some(Value::Number{value: 5})));
+        let found = type_problems(&[Operation::BumpPlus{cell: Location::Address(0)}], &initial);
+
+        assert_eq!(misuses(found.clone()), vec!((0, Misuse::BumpedLetter{cell: Location::Address(0)}, false)));
+        assert_eq!(found[0].to_string(), "instruction 0 (BumpPlus { cell: Address(0) }): may bump a letter in the tile cell 0 points to");
+
+        let numbers = InternalState::new(None, 0).with_memory(vec!(Some(Value::Number{value: 1}), Some(Value::Number{value: 5})));
+        assert_eq!(type_problems(&[Operation::BumpMinus{cell: Location::Address(0)}], &numbers), vec!());
+    }
+
+    #[test]
+    fn semantics_decide_mixed_arithmetic() {
+        // adds 1 to each item, a letter or a number
+        let operations = vec!(
+            Operation::Label,
+            Operation::Inbox,
+            Operation::Add{cell: Location::Cell(0)},
+            Operation::Outbox,
+            Operation::Jump{next_operation: 0}
+        );
+        let extended = InternalState::new(None, 0).with_memory(vec!(Some(Value::Number{value: 1})));
+        let mut game = extended.clone();
+        game.semantics = Semantics::Game;
+
+        assert_eq!(type_problems(&operations, &extended), vec!());
+        assert_eq!(type_problems(&operations, &game)[0].to_string(),
+            "instruction 2 (Add { cell: Cell(0) }): may add a number to a letter");
+    }
+}
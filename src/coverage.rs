@@ -0,0 +1,332 @@
+use serde_json;
+
+use Value;
+use Operation;
+use Program;
+use CodeIterator;
+use RunOutcome;
+use Step;
+use limits::Limits;
+use state::InternalState;
+
+// how often each instruction ran over many runs of a program, and which ways
+// its jez and jneg went, to find the code no input exercised.
+
+// the two ways out of a conditional jump
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Branches {
+    pub taken: u64,
+    pub not_taken: u64
+}
+
+impl Branches {
+    // how many of the two ways were followed at least once
+    pub fn covered(&self) -> usize {
+        (self.taken > 0) as usize + (self.not_taken > 0) as usize
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Coverage {
+    pub runs: usize,
+    // by instruction index
+    pub executions: Vec<u64>,
+    // None for instructions that are not conditional jumps
+    pub branches: Vec<Option<Branches>>
+}
+
+// whether a conditional jump goes to its label with this register
+fn jumps(operation: Operation, register: Option<Value>) -> Option<bool> {
+    match (operation, register) {
+        (Operation::JumpEqualsZero{..}, register) => Some(register == Some(Value::Number{value: 0})),
+        (Operation::JumpNegative{..}, Some(Value::Number{value})) => Some(value < 0),
+        // jneg fails on anything else
+        _ => None
+    }
+}
+
+// labels are not instructions in the game
+fn is_instruction(operation: &Operation) -> bool {
+    *operation != Operation::Label
+}
+
+impl Coverage {
+    pub fn new(operations: &[Operation]) -> Self {
+        Coverage {
+            runs: 0,
+            executions: vec!(0; operations.len()),
+            branches: operations.iter()
+                .map(|operation| match *operation {
+                    Operation::JumpEqualsZero{..} | Operation::JumpNegative{..} => Some(Branches::default()),
+                    _ => None
+                })
+                .collect()
+        }
+    }
+
+    // runs the program from the state, counting what it executes; an instruction
+    // stopping the program with an error does not count as executed
+    pub fn record(&mut self, operations: &[Operation], mut state: InternalState, limits: Limits) -> RunOutcome {
+        // a wrong outbox item would stop the run before the code after it
+        state.expected_output = None;
+        let mut iterator = CodeIterator::without_dump(&mut state, operations.to_vec())
+            .with_limits(limits);
+
+        loop {
+            let counter = iterator.state.instruction_counter;
+            let jumped = operations.get(counter).and_then(|operation| jumps(*operation, iterator.state.register));
+            match iterator.next() {
+                Some(Ok(Step::Executed(_))) => {
+                    self.executions[counter] += 1;
+                    match (self.branches[counter].as_mut(), jumped) {
+                        (Some(branches), Some(true)) => branches.taken += 1,
+                        (Some(branches), Some(false)) => branches.not_taken += 1,
+                        _ => ()
+                    }
+                },
+                _ => break
+            }
+        }
+        self.runs += 1;
+        // the iterator already stopped, this only tells why
+        iterator.run()
+    }
+
+    // adds the counts of other runs of the same program
+    pub fn merge(&mut self, other: &Coverage) {
+        self.runs += other.runs;
+        for (executions, other) in self.executions.iter_mut().zip(&other.executions) {
+            *executions += other;
+        }
+        for (branches, other) in self.branches.iter_mut().zip(&other.branches) {
+            if let (Some(branches), Some(other)) = (branches.as_mut(), other) {
+                branches.taken += other.taken;
+                branches.not_taken += other.not_taken;
+            }
+        }
+    }
+
+    // instructions executed at least once, of all of them
+    pub fn instructions(&self, operations: &[Operation]) -> (usize, usize) {
+        let counted: Vec<u64> = operations.iter().zip(&self.executions)
+            .filter(|&(operation, _)| is_instruction(operation))
+            .map(|(_, &executions)| executions)
+            .collect();
+        (counted.iter().filter(|&&executions| executions > 0).count(), counted.len())
+    }
+
+    // ways out of the conditional jumps followed at least once, of all of them
+    pub fn branches_covered(&self) -> (usize, usize) {
+        let branches: Vec<&Branches> = self.branches.iter().flatten().collect();
+        (branches.iter().map(|branches| branches.covered()).sum(), 2 * branches.len())
+    }
+
+    fn summary(&self, operations: &[Operation]) -> String {
+        let (executed, instructions) = self.instructions(operations);
+        let (covered, branches) = self.branches_covered();
+        format!("{} of {} instructions executed, {} of {} branches taken, over {} runs\n",
+            executed, instructions, covered, branches, self.runs)
+    }
+
+    // the program with the executions on the left, ##### for the instructions never run
+    pub fn to_listing(&self, program: &Program) -> String {
+        let mut listing = String::new();
+        for (index, operation) in program.operations.iter().enumerate() {
+            let executions = match self.executions[index] {
+                _ if !is_instruction(operation) => String::new(),
+                0 => String::from("#####"),
+                executions => executions.to_string()
+            };
+            let label = program.label_at(index).map(|label| format!(" ({}:)", label)).unwrap_or_default();
+            let branches = match self.branches[index] {
+                Some(branches) => format!("  [taken {}, not taken {}]", branches.taken, branches.not_taken),
+                None => String::new()
+            };
+            listing.push_str(&format!("{:>8}  {}: {:?}{}{}\n", executions, index, operation, label, branches));
+        }
+        listing.push_str(&self.summary(&program.operations));
+        listing
+    }
+
+    pub fn to_json(&self, program: &Program) -> String {
+        let (executed, instructions) = self.instructions(&program.operations);
+        let (covered, branches) = self.branches_covered();
+        let report = JsonCoverage {
+            runs: self.runs,
+            instructions,
+            executed,
+            branches,
+            branches_covered: covered,
+            lines: program.operations.iter().enumerate()
+                .map(|(index, operation)| JsonLine {
+                    instruction: index,
+                    operation: format!("{:?}", operation),
+                    label: program.label_at(index),
+                    executions: self.executions[index],
+                    taken: self.branches[index].map(|branches| branches.taken),
+                    not_taken: self.branches[index].map(|branches| branches.not_taken)
+                })
+                .collect()
+        };
+        serde_json::to_string(&report).unwrap()
+    }
+
+    // lcov tracefile keyed by instruction: line N is instruction N - 1, labels are functions
+    pub fn to_lcov(&self, program: &Program, source_name: &str) -> String {
+        let mut lcov = format!("TN:\nSF:{}\n", source_name);
+        let mut labels: Vec<&(String, usize)> = program.labels.iter().collect();
+        labels.sort_by_key(|label| label.1);
+        for &&(ref name, position) in &labels {
+            lcov.push_str(&format!("FN:{},{}\n", position + 1, name));
+        }
+        for &&(ref name, position) in &labels {
+            lcov.push_str(&format!("FNDA:{},{}\n", self.executions[position], name));
+        }
+        let reached = labels.iter().filter(|label| self.executions[label.1] > 0).count();
+        lcov.push_str(&format!("FNF:{}\nFNH:{}\n", labels.len(), reached));
+
+        for (index, operation) in program.operations.iter().enumerate() {
+            if !is_instruction(operation) {
+                continue;
+            }
+            let executions = self.executions[index];
+            if let Some(branches) = self.branches[index] {
+                // lcov tells apart jumps that never ran from ways never followed
+                let count = |count: u64| if executions == 0 { String::from("-") } else { count.to_string() };
+                lcov.push_str(&format!("BRDA:{},0,0,{}\n", index + 1, count(branches.taken)));
+                lcov.push_str(&format!("BRDA:{},0,1,{}\n", index + 1, count(branches.not_taken)));
+            }
+            lcov.push_str(&format!("DA:{},{}\n", index + 1, executions));
+        }
+
+        let (covered, branches) = self.branches_covered();
+        let (executed, instructions) = self.instructions(&program.operations);
+        lcov.push_str(&format!("BRF:{}\nBRH:{}\nLF:{}\nLH:{}\nend_of_record\n", branches, covered, instructions, executed));
+        lcov
+    }
+}
+
+#[derive(Serialize)]
+struct JsonLine<'a> {
+    instruction: usize,
+    operation: String,
+    label: Option<&'a str>,
+    executions: u64,
+    // only for conditional jumps
+    taken: Option<u64>,
+    not_taken: Option<u64>
+}
+
+#[derive(Serialize)]
+struct JsonCoverage<'a> {
+    runs: usize,
+    instructions: usize,
+    executed: usize,
+    branches: usize,
+    branches_covered: usize,
+    lines: Vec<JsonLine<'a>>
+}
+
+// runs the program from every state, adding up the counts
+pub fn coverage(operations: &[Operation], states: Vec<InternalState>, limits: Limits) -> Coverage {
+    let mut coverage = Coverage::new(operations);
+    for state in states {
+        coverage.record(operations, state, limits);
+    }
+    coverage
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json;
+
+    use Value;
+    use Location;
+    use Operation;
+    use Program;
+    use RunOutcome;
+    use limits::Limits;
+    use state::InternalState;
+    use coverage::{Branches, Coverage, coverage};
+
+    // outputs the negative items, skipping the others, until the first zero
+    fn program() -> Program {
+        Program {
+            operations: vec!(
+                Operation::Label,
+                Operation::Inbox,
+                Operation::JumpEqualsZero{next_operation: 7},
+                Operation::JumpNegative{next_operation: 5},
+                Operation::Jump{next_operation: 0},
+                Operation::Outbox,
+                Operation::Jump{next_operation: 0},
+                Operation::Label,
+                Operation::CopyFrom{cell: Location::Cell(0)}
+            ),
+            labels: vec!((String::from("start"), 0), (String::from("done"), 7))
+        }
+    }
+
+    fn with_inbox(items: Vec<i32>) -> InternalState {
+        InternalState::new(None, 0)
+            .with_input_tape(items.into_iter().rev().map(|value| Value::Number{value}).collect())
+            .with_memory(vec!(Some(Value::Number{value: 7})))
+    }
+
+    #[test]
+    fn counts_executions_and_branches() {
+        let program = program();
+        let found = coverage(&program.operations, vec!(with_inbox(vec!(3, 0)), with_inbox(vec!(2, 5))), Limits::default());
+
+        assert_eq!(found.runs, 2);
+        assert_eq!(found.executions, vec!(5, 4, 4, 3, 3, 0, 0, 1, 1));
+        assert_eq!(found.branches[2], Some(Branches{taken: 1, not_taken: 3}));
+        assert_eq!(found.branches[3], Some(Branches{taken: 0, not_taken: 3}));
+        assert_eq!(found.branches[4], None);
+        assert_eq!(found.instructions(&program.operations), (5, 7));
+        assert_eq!(found.branches_covered(), (3, 4));
+    }
+
+    #[test]
+    fn merges_runs() {
+        let program = program();
+        let mut first = Coverage::new(&program.operations);
+        assert_eq!(first.record(&program.operations, with_inbox(vec!(-1)), Limits::default()), RunOutcome::CompletedOnEmptyInbox);
+        let mut second = Coverage::new(&program.operations);
+        second.record(&program.operations, with_inbox(vec!(0)), Limits::default());
+        first.merge(&second);
+
+        assert_eq!(first.runs, 2);
+        assert_eq!(first.executions, vec!(3, 2, 2, 1, 0, 1, 1, 1, 1));
+        assert_eq!(first.branches_covered(), (3, 4));
+    }
+
+    #[test]
+    fn reports() {
+        let program = program();
+        let found = coverage(&program.operations, vec!(with_inbox(vec!(0))), Limits::default());
+
+        assert_eq!(found.to_listing(&program), "          0: Label (start:)
+       1  1: Inbox
+       1  2: JumpEqualsZero { next_operation: 7 }  [taken 1, not taken 0]
+   #####  3: JumpNegative { next_operation: 5 }  [taken 0, not taken 0]
+   #####  4: Jump { next_operation: 0 }
+   #####  5: Outbox
+   #####  6: Jump { next_operation: 0 }
+          7: Label (done:)
+       1  8: CopyFrom { cell: Cell(0) }
+3 of 7 instructions executed, 1 of 4 branches taken, over 1 runs
+");
+        assert_eq!(found.to_lcov(&program, "negative.hrm"), "TN:\nSF:negative.hrm\n\
+            FN:1,start\nFN:8,done\nFNDA:1,start\nFNDA:1,done\nFNF:2\nFNH:2\n\
+            DA:2,1\nBRDA:3,0,0,1\nBRDA:3,0,1,0\nDA:3,1\nBRDA:4,0,0,-\nBRDA:4,0,1,-\nDA:4,0\n\
+            DA:5,0\nDA:6,0\nDA:7,0\nDA:9,1\n\
+            BRF:4\nBRH:1\nLF:7\nLH:3\nend_of_record\n");
+
+        let json: serde_json::Value = serde_json::from_str(&found.to_json(&program)).unwrap();
+        assert_eq!(json["executed"], 3);
+        assert_eq!(json["lines"][0]["label"], "start");
+        assert_eq!(json["lines"][2]["taken"], 1);
+        assert_eq!(json["lines"][1]["taken"], serde_json::Value::Null);
+    }
+}
@@ -0,0 +1,639 @@
+use std::cmp::Ordering;
+
+use Value;
+use Location;
+use describe_value;
+use Operation;
+use memory::extract_memory_position;
+use analysis::location_of;
+use state::InternalState;
+
+// debugging hooks checked by CodeIterator around every instruction:
+// breakpoints pause the execution before it, watchpoints after it,
+// tracepoints only log a snapshot of the state.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Subject {
+    Register,
+    Cell(usize)
+}
+
+fn value_of(subject: Subject, s: &InternalState) -> Option<Value> {
+    match subject {
+        Subject::Register => s.register,
+        Subject::Cell(cell) => s.memory.get(cell).cloned().unwrap_or(None)
+    }
+}
+
+// an empty register or cell is only different from any value,
+// and numbers and letters can only be told equal or different.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Condition {
+    pub subject: Subject,
+    pub comparison: Comparison,
+    pub value: Value
+}
+
+impl Condition {
+    pub fn new(subject: Subject, comparison: Comparison, value: Value) -> Self {
+        Condition{subject, comparison, value}
+    }
+
+    pub fn holds(&self, s: &InternalState) -> bool {
+        let ordering = match (value_of(self.subject, s), self.value) {
+            (Some(Value::Number{value: current}), Value::Number{value: expected}) =>
+                Some(current.cmp(&expected)),
+            (Some(Value::Character{value: current}), Value::Character{value: expected}) =>
+                Some(current.cmp(&expected)),
+            _ => None
+        };
+
+        match (self.comparison, ordering) {
+            (Comparison::Equal, ordering) => ordering == Some(Ordering::Equal),
+            (Comparison::NotEqual, ordering) => ordering != Some(Ordering::Equal),
+            (Comparison::Less, Some(ordering)) => ordering == Ordering::Less,
+            (Comparison::LessOrEqual, Some(ordering)) => ordering != Ordering::Greater,
+            (Comparison::Greater, Some(ordering)) => ordering == Ordering::Greater,
+            (Comparison::GreaterOrEqual, Some(ordering)) => ordering != Ordering::Less,
+            (_, None) => false
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Position {
+    Instruction(usize),
+    Label(String)
+}
+
+// without a position, the breakpoint is checked before every instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Breakpoint {
+    pub position: Option<Position>,
+    pub condition: Option<Condition>
+}
+
+impl Breakpoint {
+    pub fn at_instruction(instruction: usize) -> Self {
+        Breakpoint{position: Some(Position::Instruction(instruction)), condition: None}
+    }
+
+    pub fn at_label(label: &str) -> Self {
+        Breakpoint{position: Some(Position::Label(String::from(label))), condition: None}
+    }
+
+    pub fn on_condition(condition: Condition) -> Self {
+        Breakpoint{position: None, condition: Some(condition)}
+    }
+
+    pub fn when(mut self, condition: Condition) -> Self {
+        self.condition = Some(condition);
+        self
+    }
+}
+
+// format can refer to the state with {counter}, {executed}, {register},
+// {inbox}, {outbox}, {memory} and {cell:N}.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tracepoint {
+    pub position: Position,
+    pub format: String
+}
+
+impl Tracepoint {
+    pub fn new(position: Position, format: &str) -> Self {
+        Tracepoint{position, format: String::from(format)}
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WatchTarget {
+    Register,
+    Cell(usize),
+    // whatever tile cell N points to when an instruction runs
+    PointedBy(usize)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WatchKind {
+    Write,
+    Read,
+    // a write leaving a value that satisfies the comparison
+    Value(Comparison, Value)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Watchpoint {
+    pub target: WatchTarget,
+    pub kind: WatchKind
+}
+
+impl Watchpoint {
+    pub fn on_write(target: WatchTarget) -> Self {
+        Watchpoint{target, kind: WatchKind::Write}
+    }
+
+    pub fn on_read(target: WatchTarget) -> Self {
+        Watchpoint{target, kind: WatchKind::Read}
+    }
+
+    pub fn on_value(target: WatchTarget, comparison: Comparison, value: Value) -> Self {
+        Watchpoint{target, kind: WatchKind::Value(comparison, value)}
+    }
+
+    fn subject(&self, s: &InternalState) -> Option<Subject> {
+        match self.target {
+            WatchTarget::Register => Some(Subject::Register),
+            WatchTarget::Cell(cell) => Some(Subject::Cell(cell)),
+            WatchTarget::PointedBy(cell) => extract_memory_position(Location::Address(cell), s)
+                .ok()
+                .map(Subject::Cell)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    Read,
+    Write
+}
+
+// what an operation is going to read and write, pointers included
+pub fn accesses(operation: Operation, s: &InternalState) -> Vec<(Subject, Access)> {
+    let location = location_of(operation);
+
+    let mut accesses = vec!();
+    if let Some(Location::Address(pointer)) = location {
+        accesses.push((Subject::Cell(pointer), Access::Read));
+    }
+    let tile = location.and_then(|location| extract_memory_position(location, s).ok()).map(Subject::Cell);
+    let (reads, writes) = match operation {
+        Operation::Inbox => (vec!(), vec!(Some(Subject::Register))),
+        Operation::Outbox | Operation::JumpEqualsZero{..} | Operation::JumpNegative{..} =>
+            (vec!(Some(Subject::Register)), vec!()),
+        Operation::CopyFrom{..} => (vec!(tile), vec!(Some(Subject::Register))),
+        Operation::CopyTo{..} => (vec!(Some(Subject::Register)), vec!(tile)),
+        Operation::Add{..} | Operation::Sub{..} =>
+            (vec!(Some(Subject::Register), tile), vec!(Some(Subject::Register))),
+        Operation::BumpPlus{..} | Operation::BumpMinus{..} =>
+            (vec!(tile), vec!(tile, Some(Subject::Register))),
+        Operation::Label | Operation::Jump{..} => (vec!(), vec!())
+    };
+    accesses.extend(reads.into_iter().flatten().map(|subject| (subject, Access::Read)));
+    accesses.extend(writes.into_iter().flatten().map(|subject| (subject, Access::Write)));
+
+    accesses
+}
+
+// why CodeIterator paused
+#[derive(Debug, Clone, PartialEq)]
+pub enum Hit {
+    Breakpoint{id: usize, instruction: usize},
+    // reported once the instruction ran; a read leaves the value unchanged
+    Watchpoint{
+        id: usize,
+        instruction: usize,
+        operation: Operation,
+        subject: Subject,
+        access: Access,
+        old_value: Option<Value>,
+        new_value: Option<Value>
+    }
+}
+
+fn describe_all(values: &[Option<Value>]) -> String {
+    let described: Vec<String> = values.iter().map(|&value| describe_value(value, "_")).collect();
+    format!("[{}]", described.join(", "))
+}
+
+fn placeholder_value(placeholder: &str, s: &InternalState) -> Option<String> {
+    match placeholder {
+        "counter" => Some(s.instruction_counter.to_string()),
+        "executed" => Some(s.executed_instructions().to_string()),
+        "register" => Some(describe_value(s.register, "_")),
+        // next value to be picked first, like in the game
+        "inbox" => Some(describe_all(&s.input_tape.iter().rev().cloned().map(Some).collect::<Vec<_>>())),
+        "outbox" => Some(describe_all(&s.output_tape.iter().cloned().map(Some).collect::<Vec<_>>())),
+        "memory" => Some(describe_all(&s.memory)),
+        _ if placeholder.starts_with("cell:") => placeholder[5..].trim().parse::<usize>().ok()
+            .map(|cell| describe_value(s.memory.get(cell).cloned().unwrap_or(None), "_")),
+        _ => None
+    }
+}
+
+// unknown placeholders are left as they are
+pub fn format_snapshot(format: &str, s: &InternalState) -> String {
+    let mut result = String::new();
+    let mut rest = format;
+
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let replaced = rest.find('}').and_then(|end| {
+            placeholder_value(&rest[1..end], s).map(|value| (value, end))
+        });
+        match replaced {
+            Some((value, end)) => {
+                result.push_str(&value);
+                rest = &rest[end + 1..];
+            },
+            None => {
+                result.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+
+    result
+}
+
+pub struct Hooks {
+    breakpoints: Vec<(usize, Breakpoint)>,
+    tracepoints: Vec<(usize, Tracepoint)>,
+    watchpoints: Vec<(usize, Watchpoint)>,
+    // watchpoints touched by the running instruction, with the old values
+    watched: Vec<(usize, Subject, Access, Option<Value>)>,
+    next_id: usize,
+    labels: Vec<(String, usize)>,
+    messages: Vec<String>
+}
+
+impl Hooks {
+    pub fn new(labels: Vec<(String, usize)>) -> Self {
+        Hooks{
+            breakpoints: vec!(),
+            tracepoints: vec!(),
+            watchpoints: vec!(),
+            watched: vec!(),
+            next_id: 1,
+            labels,
+            messages: vec!()
+        }
+    }
+
+    fn new_id(&mut self) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    pub fn labels(&self) -> &[(String, usize)] {
+        &self.labels
+    }
+
+    // keeps the hooks already added; the ones on labels follow the new table
+    pub fn set_labels(&mut self, labels: Vec<(String, usize)>) {
+        self.labels = labels;
+    }
+
+    pub fn resolve(&self, position: &Position) -> Option<usize> {
+        match *position {
+            Position::Instruction(instruction) => Some(instruction),
+            Position::Label(ref label) => self.labels.iter()
+                .rev()
+                .find(|pair| pair.0 == *label)
+                .map(|pair| pair.1)
+        }
+    }
+
+    // returns the id used to remove it
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> usize {
+        let id = self.new_id();
+        self.breakpoints.push((id, breakpoint));
+        id
+    }
+
+    pub fn remove_breakpoint(&mut self, id: usize) -> bool {
+        let before = self.breakpoints.len();
+        self.breakpoints.retain(|pair| pair.0 != id);
+        self.breakpoints.len() != before
+    }
+
+    pub fn breakpoints(&self) -> &[(usize, Breakpoint)] {
+        &self.breakpoints
+    }
+
+    pub fn add_tracepoint(&mut self, tracepoint: Tracepoint) -> usize {
+        let id = self.new_id();
+        self.tracepoints.push((id, tracepoint));
+        id
+    }
+
+    pub fn remove_tracepoint(&mut self, id: usize) -> bool {
+        let before = self.tracepoints.len();
+        self.tracepoints.retain(|pair| pair.0 != id);
+        self.tracepoints.len() != before
+    }
+
+    pub fn tracepoints(&self) -> &[(usize, Tracepoint)] {
+        &self.tracepoints
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) -> usize {
+        let id = self.new_id();
+        self.watchpoints.push((id, watchpoint));
+        id
+    }
+
+    pub fn remove_watchpoint(&mut self, id: usize) -> bool {
+        let before = self.watchpoints.len();
+        self.watchpoints.retain(|pair| pair.0 != id);
+        self.watchpoints.len() != before
+    }
+
+    pub fn watchpoints(&self) -> &[(usize, Watchpoint)] {
+        &self.watchpoints
+    }
+
+    // messages logged by tracepoints since the last call
+    pub fn take_messages(&mut self) -> Vec<String> {
+        self.messages.drain(..).collect()
+    }
+
+    fn is_at(&self, position: &Option<Position>, instruction: usize) -> bool {
+        match *position {
+            Some(ref position) => self.resolve(position) == Some(instruction),
+            None => true
+        }
+    }
+
+    // logs the tracepoints and looks for a breakpoint on the next instruction
+    pub fn before_instruction(&mut self, s: &InternalState) -> Option<Hit> {
+        let instruction = s.instruction_counter;
+
+        let messages: Vec<String> = self.tracepoints.iter()
+            .filter(|pair| self.resolve(&pair.1.position) == Some(instruction))
+            .map(|pair| format_snapshot(&pair.1.format, s))
+            .collect();
+        self.messages.extend(messages);
+
+        self.breakpoints.iter()
+            .find(|pair| {
+                let breakpoint = &pair.1;
+                self.is_at(&breakpoint.position, instruction)
+                    && breakpoint.condition.is_none_or(|condition| condition.holds(s))
+            })
+            .map(|pair| Hit::Breakpoint{id: pair.0, instruction})
+    }
+
+    // remembers which watchpoints the operation is going to touch
+    pub fn before_access(&mut self, operation: Operation, s: &InternalState) {
+        self.watched.clear();
+        if self.watchpoints.is_empty() {
+            return;
+        }
+
+        let accesses = accesses(operation, s);
+        for &(id, ref watchpoint) in &self.watchpoints {
+            let subject = match watchpoint.subject(s) {
+                Some(subject) => subject,
+                None => continue
+            };
+            let wanted = match watchpoint.kind {
+                WatchKind::Read => Access::Read,
+                WatchKind::Write | WatchKind::Value(..) => Access::Write
+            };
+            if accesses.contains(&(subject, wanted)) {
+                self.watched.push((id, subject, wanted, value_of(subject, s)));
+            }
+        }
+    }
+
+    // the watchpoints hit by the instruction that just ran, in the order they were added
+    pub fn after_access(&mut self, instruction: usize, operation: Operation, s: &InternalState) -> Vec<Hit> {
+        let watched: Vec<_> = self.watched.drain(..).collect();
+        watched.into_iter()
+            .filter(|&(id, subject, _, _)| {
+                match self.watchpoints.iter().find(|pair| pair.0 == id).map(|pair| pair.1.kind) {
+                    Some(WatchKind::Value(comparison, value)) => Condition::new(subject, comparison, value).holds(s),
+                    Some(_) => true,
+                    None => false
+                }
+            })
+            .map(|(id, subject, access, old_value)| Hit::Watchpoint{
+                id,
+                instruction,
+                operation,
+                subject,
+                access,
+                old_value,
+                new_value: value_of(subject, s)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use Value;
+    use Operation;
+    use Location;
+    use Step;
+    use CodeIterator;
+    use state::InternalState;
+    use debug::{Breakpoint, Tracepoint, Watchpoint, WatchTarget, Access, Condition, Comparison, Subject, Position, Hit, format_snapshot};
+
+    fn program() -> Vec<Operation> {
+        vec!(
+            Operation::Label,
+            Operation::Inbox,
+            Operation::CopyTo{cell: Location::Cell(0)},
+            Operation::Outbox,
+            Operation::Jump{next_operation: 0}
+        )
+    }
+
+    #[test]
+    fn condition_on_register() {
+        let state = InternalState::new(Some(Value::Number{value: 3}), 0);
+
+        assert!(Condition::new(Subject::Register, Comparison::Equal, Value::Number{value: 3}).holds(&state));
+        assert!(Condition::new(Subject::Register, Comparison::Greater, Value::Number{value: 2}).holds(&state));
+        assert!(!Condition::new(Subject::Register, Comparison::Less, Value::Character{value: 'a'}).holds(&state));
+        assert!(Condition::new(Subject::Register, Comparison::NotEqual, Value::Character{value: 'a'}).holds(&state));
+    }
+
+    #[test]
+    fn condition_on_empty_cell() {
+        let state = InternalState::new(None, 0).with_memory(vec!(None));
+
+        assert!(!Condition::new(Subject::Cell(0), Comparison::Equal, Value::Number{value: 0}).holds(&state));
+        assert!(Condition::new(Subject::Cell(0), Comparison::NotEqual, Value::Number{value: 0}).holds(&state));
+        assert!(!Condition::new(Subject::Cell(7), Comparison::Equal, Value::Number{value: 0}).holds(&state));
+    }
+
+    #[test]
+    fn snapshot_format() {
+        let state = InternalState::new(Some(Value::Character{value: 'a'}), 2)
+            .with_input_tape(vec!(Value::Number{value: 1}, Value::Number{value: 2}))
+            .with_memory(vec!(None, Some(Value::Number{value: -4})));
+
+        let result = format_snapshot("{counter}: R={register} [1]={cell:1} in={inbox} {unknown} {", &state);
+
+        assert_eq!(result, "2: R=a [1]=-4 in=[2, 1] {unknown} {");
+    }
+
+    #[test]
+    fn pauses_at_label_breakpoint() {
+        let mut state = InternalState::new(None, 0)
+            .with_input_tape(vec!(Value::Number{value: 1}, Value::Number{value: 2}))
+            .with_memory(vec!(None));
+        let mut iterator = CodeIterator::without_dump(&mut state, program())
+            .with_labels(vec!((String::from("start"), 0)));
+        let id = iterator.hooks.add_breakpoint(Breakpoint::at_label("start"));
+
+        assert_eq!(iterator.next(), Some(Ok(Step::Paused(Hit::Breakpoint{id, instruction: 0}))));
+        // resuming runs the instruction the breakpoint is on
+        assert!(match iterator.next() {
+            Some(Ok(Step::Executed(_))) => true,
+            _ => false
+        });
+        let executed = iterator.by_ref()
+            .take_while(|step| match *step {
+                Ok(Step::Executed(_)) => true,
+                _ => false
+            })
+            .count();
+
+        assert_eq!(executed, 4);
+        assert_eq!(iterator.state.output_tape, vec!(Value::Number{value: 2}));
+    }
+
+    #[test]
+    fn pauses_on_condition() {
+        let mut state = InternalState::new(None, 0)
+            .with_input_tape(vec!(Value::Number{value: 7}, Value::Number{value: 5}))
+            .with_memory(vec!(None));
+        let mut iterator = CodeIterator::without_dump(&mut state, program());
+        iterator.hooks.add_breakpoint(Breakpoint::at_instruction(3)
+            .when(Condition::new(Subject::Cell(0), Comparison::Equal, Value::Number{value: 7})));
+
+        let paused = iterator.by_ref().find(|step| match *step {
+            Ok(Step::Paused(_)) => true,
+            _ => false
+        });
+
+        assert!(paused.is_some());
+        assert_eq!(iterator.state.instruction_counter, 3);
+        assert_eq!(iterator.state.output_tape, vec!(Value::Number{value: 5}));
+    }
+
+    #[test]
+    fn tracepoints_do_not_pause() {
+        let mut state = InternalState::new(None, 0)
+            .with_input_tape(vec!(Value::Number{value: 7}, Value::Number{value: 5}))
+            .with_memory(vec!(None));
+        let mut iterator = CodeIterator::without_dump(&mut state, program());
+        iterator.hooks.add_tracepoint(Tracepoint::new(Position::Instruction(3), "outbox {register}"));
+
+        let paused = iterator.by_ref().any(|step| match step {
+            Ok(Step::Paused(_)) => true,
+            _ => false
+        });
+
+        assert!(!paused);
+        assert_eq!(iterator.hooks.take_messages(), vec!("outbox 5", "outbox 7"));
+        assert!(iterator.hooks.take_messages().is_empty());
+    }
+
+    fn first_hit(iterator: &mut CodeIterator) -> Option<Hit> {
+        iterator.find_map(|step| match step {
+            Ok(Step::Paused(hit)) => Some(hit),
+            _ => None
+        })
+    }
+
+    #[test]
+    fn watches_cell_writes() {
+        let mut state = InternalState::new(None, 0)
+            .with_input_tape(vec!(Value::Number{value: 7}, Value::Number{value: 5}))
+            .with_memory(vec!(None));
+        let mut iterator = CodeIterator::without_dump(&mut state, program());
+        let id = iterator.hooks.add_watchpoint(Watchpoint::on_write(WatchTarget::Cell(0)));
+
+        assert_eq!(first_hit(&mut iterator), Some(Hit::Watchpoint{
+            id,
+            instruction: 2,
+            operation: Operation::CopyTo{cell: Location::Cell(0)},
+            subject: Subject::Cell(0),
+            access: Access::Write,
+            old_value: None,
+            new_value: Some(Value::Number{value: 5})
+        }));
+        // the hit is reported after the instruction ran
+        assert_eq!(iterator.state.instruction_counter, 3);
+        assert_eq!(first_hit(&mut iterator).map(|hit| match hit {
+            Hit::Watchpoint{old_value, new_value, ..} => (old_value, new_value),
+            _ => (None, None)
+        }), Some((Some(Value::Number{value: 5}), Some(Value::Number{value: 7}))));
+    }
+
+    #[test]
+    fn reports_every_watchpoint_of_an_instruction() {
+        let mut state = InternalState::new(None, 0)
+            .with_input_tape(vec!(Value::Number{value: 5}))
+            .with_memory(vec!(None));
+        let mut iterator = CodeIterator::without_dump(&mut state, program());
+        let written = iterator.hooks.add_watchpoint(Watchpoint::on_write(WatchTarget::Cell(0)));
+        let read = iterator.hooks.add_watchpoint(Watchpoint::on_read(WatchTarget::Register));
+
+        // copyto reads the register and writes the cell
+        let hits: Vec<_> = (0..2).filter_map(|_| match first_hit(&mut iterator) {
+            Some(Hit::Watchpoint{id, instruction, ..}) => Some((id, instruction)),
+            _ => None
+        }).collect();
+
+        assert_eq!(hits, vec!((written, 2), (read, 2)));
+        assert_eq!(iterator.state.instruction_counter, 3);
+    }
+
+    #[test]
+    fn watches_register_reads() {
+        let mut state = InternalState::new(None, 0)
+            .with_input_tape(vec!(Value::Number{value: 5}))
+            .with_memory(vec!(None));
+        let mut iterator = CodeIterator::without_dump(&mut state, program());
+        iterator.hooks.add_watchpoint(Watchpoint::on_read(WatchTarget::Register));
+
+        let instruction = match first_hit(&mut iterator) {
+            Some(Hit::Watchpoint{instruction, access: Access::Read, ..}) => instruction,
+            _ => 0
+        };
+
+        assert_eq!(instruction, 2);
+    }
+
+    #[test]
+    fn watches_pointed_cells_and_values() {
+        let operations = vec!(
+            Operation::BumpPlus{cell: Location::Address(0)},
+            Operation::Jump{next_operation: 0}
+        );
+        let mut state = InternalState::new(None, 0)
+            .with_memory(vec!(Some(Value::Number{value: 1}), Some(Value::Number{value: 0})));
+        let mut iterator = CodeIterator::without_dump(&mut state, operations);
+        let pointed = iterator.hooks.add_watchpoint(Watchpoint::on_write(WatchTarget::PointedBy(0)));
+
+        assert!(match first_hit(&mut iterator) {
+            Some(Hit::Watchpoint{id, subject: Subject::Cell(1), ..}) => id == pointed,
+            _ => false
+        });
+
+        iterator.hooks.remove_watchpoint(pointed);
+        iterator.hooks.add_watchpoint(Watchpoint::on_value(WatchTarget::Cell(1), Comparison::GreaterOrEqual, Value::Number{value: 3}));
+
+        assert!(first_hit(&mut iterator).is_some());
+        assert_eq!(iterator.state.memory[1], Some(Value::Number{value: 3}));
+    }
+}
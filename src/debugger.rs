@@ -0,0 +1,723 @@
+use std::io::{self, BufRead, Write};
+
+use Value;
+use describe_value;
+use parse_value;
+use Operation;
+use Step;
+use CodeIterator;
+use RunOutcome;
+use diff::describe_outcome;
+use error::RuntimeError;
+use debug::{Breakpoint, Tracepoint, Watchpoint, WatchTarget, Access, Condition, Comparison, Subject, Position, Hit, format_snapshot};
+
+// interactive debugger reading gdb-like commands, built on CodeIterator hooks
+
+const HELP: &str = "\
+step [N]            run the next instruction (N times)
+next [N]            like step, without stopping on labels
+continue            run until a breakpoint, an error or the end of the program
+until <position>    run until an instruction index or a label
+back [N]            undo the last instruction (N instructions)
+goto <step>         go back or forward to the given number of executed instructions
+changed cell N      show the last step that changed a floor tile
+print [what]        show register, memory, cell N, inbox, outbox or everything
+list                show the program
+set register <v>    change the register (_ empties it)
+set cell N <v>      change a floor tile (_ empties it)
+break <position> [if <subject> <op> <value>]
+break if <subject> <op> <value>
+                    add a breakpoint; subject is register or cell N, op is one of == != < <= > >=
+watch <target> [if <op> <value>]
+                    pause after a write to register, cell N or pointer N (the tile cell N points to)
+rwatch <target>     pause after a read of register, cell N or pointer N
+trace <position> <format>
+                    log a snapshot, like {counter} {register} {cell:N} {inbox} {outbox}
+unbreak <id>        remove a breakpoint, a watchpoint or a tracepoint
+info                list breakpoints, watchpoints and tracepoints
+quit                leave the debugger";
+
+const SNAPSHOT: &str = "counter {counter}, executed {executed}, register {register}\n\
+    inbox {inbox}\noutbox {outbox}\nmemory {memory}";
+
+// how a run of the program stopped
+#[derive(PartialEq)]
+enum Stop {
+    Executed,
+    Paused(Hit),
+    Failed(RuntimeError),
+    // the program ended on this very command
+    Ended(RunOutcome),
+    // it had already ended before
+    NotRunning
+}
+
+pub struct Debugger<'a> {
+    pub iterator: CodeIterator<'a>,
+    finished: bool
+}
+
+// _ is an empty tile
+fn parse_tile(raw: &str) -> Result<Option<Value>, String> {
+    if raw == "_" {
+        return Ok(None);
+    }
+    parse_value(raw).map(Some)
+}
+
+fn parse_comparison(raw: &str) -> Result<Comparison, String> {
+    match raw {
+        "==" => Ok(Comparison::Equal),
+        "!=" => Ok(Comparison::NotEqual),
+        "<" => Ok(Comparison::Less),
+        "<=" => Ok(Comparison::LessOrEqual),
+        ">" => Ok(Comparison::Greater),
+        ">=" => Ok(Comparison::GreaterOrEqual),
+        _ => Err(format!("unknown comparison {}", raw))
+    }
+}
+
+fn parse_cell(raw: Option<&str>) -> Result<usize, String> {
+    let raw = raw.ok_or_else(|| String::from("missing cell"))?;
+    raw.parse::<usize>().map_err(|_| format!("{} is not a cell", raw))
+}
+
+// "register == 3" or "cell 2 < 0"
+fn parse_condition(tokens: &[&str]) -> Result<Condition, String> {
+    let (subject, rest) = match tokens.first() {
+        Some(&"register") => (Subject::Register, &tokens[1..]),
+        Some(&"cell") => (Subject::Cell(parse_cell(tokens.get(1).cloned())?), &tokens[2.min(tokens.len())..]),
+        _ => return Err(String::from("a condition starts with register or cell N"))
+    };
+    if rest.len() != 2 {
+        return Err(String::from("a condition looks like: register == 3"));
+    }
+
+    match parse_tile(rest[1])? {
+        Some(value) => Ok(Condition::new(subject, parse_comparison(rest[0])?, value)),
+        None => Err(String::from("conditions need a value"))
+    }
+}
+
+// "register", "cell N" or "pointer N", with the tokens left
+fn parse_watch_target<'t>(tokens: &'t [&'t str]) -> Result<(WatchTarget, &'t [&'t str]), String> {
+    match tokens.first() {
+        Some(&"register") => Ok((WatchTarget::Register, &tokens[1..])),
+        Some(&"cell") => Ok((WatchTarget::Cell(parse_cell(tokens.get(1).cloned())?), &tokens[2..])),
+        Some(&"pointer") => Ok((WatchTarget::PointedBy(parse_cell(tokens.get(1).cloned())?), &tokens[2..])),
+        _ => Err(String::from("watch register, cell N or pointer N"))
+    }
+}
+
+fn parse_watchpoint(tokens: &[&str], access: Access) -> Result<Watchpoint, String> {
+    let (target, rest) = parse_watch_target(tokens)?;
+    match (access, rest) {
+        (Access::Write, &[]) => Ok(Watchpoint::on_write(target)),
+        (Access::Read, &[]) => Ok(Watchpoint::on_read(target)),
+        (Access::Write, &["if", comparison, value]) => match parse_tile(value)? {
+            Some(value) => Ok(Watchpoint::on_value(target, parse_comparison(comparison)?, value)),
+            None => Err(String::from("conditions need a value"))
+        },
+        _ => Err(format!("unexpected {}", rest.join(" ")))
+    }
+}
+
+fn describe_subject(subject: Subject) -> String {
+    match subject {
+        Subject::Register => String::from("register"),
+        Subject::Cell(cell) => format!("cell {}", cell)
+    }
+}
+
+fn parse_steps(raw: Option<&&str>) -> Result<usize, String> {
+    match raw {
+        Some(raw) => raw.parse::<usize>().map_err(|_| format!("{} is not a number of steps", raw)),
+        None => Ok(1)
+    }
+}
+
+impl<'a> Debugger<'a> {
+    pub fn new(iterator: CodeIterator<'a>) -> Self {
+        Debugger{iterator, finished: false}
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    fn parse_position(&self, raw: Option<&&str>) -> Result<Position, String> {
+        let raw = raw.ok_or_else(|| String::from("missing instruction index or label"))?;
+        let position = match raw.parse::<usize>() {
+            Ok(instruction) => Position::Instruction(instruction),
+            Err(_) => Position::Label(raw.to_string())
+        };
+
+        match self.iterator.hooks.resolve(&position) {
+            Some(instruction) if instruction < self.iterator.operations.len() => Ok(position),
+            _ => Err(format!("{} is not in the program", raw))
+        }
+    }
+
+    fn advance(&mut self) -> Stop {
+        if self.finished {
+            return Stop::NotRunning;
+        }
+
+        match self.iterator.next() {
+            Some(Ok(Step::Executed(_))) => Stop::Executed,
+            Some(Ok(Step::Paused(hit))) => Stop::Paused(hit),
+            Some(Err(error)) => {
+                self.finished = true;
+                Stop::Failed(error)
+            },
+            None => {
+                self.finished = true;
+                Stop::Ended(self.iterator.end_outcome())
+            }
+        }
+    }
+
+    // runs one instruction, going past the breakpoint on it and
+    // stopping on the watchpoints it hits
+    fn step(&mut self) -> Stop {
+        let stop = match self.advance() {
+            Stop::Paused(Hit::Breakpoint{..}) => self.advance(),
+            stop => stop
+        };
+        match stop {
+            Stop::Executed => match self.advance_if_watched() {
+                Some(stop) => stop,
+                None => Stop::Executed
+            },
+            stop => stop
+        }
+    }
+
+    fn advance_if_watched(&mut self) -> Option<Stop> {
+        if self.iterator.has_pending_hit() {
+            Some(self.advance())
+        }
+        else {
+            None
+        }
+    }
+
+    fn is_at_label(&self) -> bool {
+        let counter = self.iterator.state.instruction_counter;
+        self.iterator.operations.get(counter) == Some(&Operation::Label)
+    }
+
+    fn skip_labels(&mut self) -> Stop {
+        while self.is_at_label() {
+            match self.step() {
+                Stop::Executed => (),
+                stop => return stop
+            }
+        }
+        Stop::Executed
+    }
+
+    // like step, but labels around the instruction are run too
+    fn next_instruction(&mut self) -> Stop {
+        match self.skip_labels() {
+            Stop::Executed => (),
+            stop => return stop
+        }
+        match self.step() {
+            Stop::Executed => self.skip_labels(),
+            stop => stop
+        }
+    }
+
+    fn step_back(&mut self) -> Result<(), String> {
+        if self.iterator.history.is_none() {
+            return Err(String::from("the history is not being recorded"));
+        }
+        match self.iterator.step_back() {
+            Some(_) => {
+                self.finished = false;
+                Ok(())
+            },
+            None => Err(String::from("already at the oldest recorded step"))
+        }
+    }
+
+    // recorded steps are restored, later ones are executed
+    fn goto(&mut self, step: u32) -> Stop {
+        if self.iterator.travel_to(step) {
+            self.finished = false;
+            return Stop::Executed;
+        }
+
+        let mut stop = Stop::Executed;
+        while stop == Stop::Executed && self.iterator.state.executed_instructions() < step {
+            stop = self.step();
+        }
+        stop
+    }
+
+    fn last_change<W: Write>(&self, tokens: &[&str], out: &mut W) -> io::Result<()> {
+        let cell = match tokens.first() {
+            Some(&"cell") => parse_cell(tokens.get(1).cloned()),
+            _ => Err(String::from("usage: changed cell N"))
+        };
+        let cell = match cell {
+            Ok(cell) => cell,
+            Err(reason) => return writeln!(out, "{}", reason)
+        };
+
+        match self.iterator.history.as_ref().and_then(|history| history.last_change_of_cell(cell)) {
+            Some(delta) => writeln!(out, "cell {} changed at step {} by {}",
+                cell, delta.step, self.describe_instruction(delta.instruction)),
+            None => writeln!(out, "no recorded step changed cell {}", cell)
+        }
+    }
+
+    fn describe_instruction(&self, index: usize) -> String {
+        let operation = match self.iterator.operations.get(index) {
+            Some(operation) => format!("{:?}", operation),
+            None => String::from("<end of program>")
+        };
+        let label = self.iterator.hooks.labels().iter()
+            .find(|pair| pair.1 == index)
+            .map(|pair| format!(" ({}:)", pair.0))
+            .unwrap_or_default();
+
+        format!("{}: {}{}", index, operation, label)
+    }
+
+    fn write_watchpoint<W: Write>(&self, hit: Hit, out: &mut W) -> io::Result<()> {
+        match hit {
+            Hit::Watchpoint{id, instruction, subject, access: Access::Read, old_value, ..} =>
+                writeln!(out, "watchpoint {}: {} read by {}: {}", id, describe_subject(subject),
+                    self.describe_instruction(instruction), describe_value(old_value, "_")),
+            Hit::Watchpoint{id, instruction, subject, access: Access::Write, old_value, new_value, ..} =>
+                writeln!(out, "watchpoint {}: {} written by {}: {} -> {}", id, describe_subject(subject),
+                    self.describe_instruction(instruction), describe_value(old_value, "_"), describe_value(new_value, "_")),
+            Hit::Breakpoint{..} => Ok(())
+        }
+    }
+
+    fn report<W: Write>(&mut self, stop: Stop, out: &mut W) -> io::Result<()> {
+        for message in self.iterator.hooks.take_messages() {
+            writeln!(out, "trace: {}", message)?;
+        }
+
+        match stop {
+            Stop::Executed => (),
+            Stop::Paused(Hit::Breakpoint{id, instruction}) =>
+                writeln!(out, "breakpoint {} hit at instruction {}", id, instruction)?,
+            Stop::Paused(hit) => {
+                self.write_watchpoint(hit, out)?;
+                // the other watchpoints the same instruction hit
+                while self.iterator.has_pending_hit() {
+                    if let Stop::Paused(hit) = self.advance() {
+                        self.write_watchpoint(hit, out)?;
+                    }
+                }
+            },
+            Stop::Failed(error) => writeln!(out, "error: {}", error)?,
+            Stop::Ended(RunOutcome::CompletedOnEmptyInbox) => return writeln!(out, "program finished: inbox empty"),
+            Stop::Ended(RunOutcome::CompletedAtEnd) => return writeln!(out, "program finished: past the last instruction"),
+            Stop::Ended(outcome) => return writeln!(out, "program stopped by {}", describe_outcome(&outcome)),
+            Stop::NotRunning => return writeln!(out, "the program is not running"),
+        }
+        let counter = self.iterator.state.instruction_counter;
+        writeln!(out, "=> {}", self.describe_instruction(counter))
+    }
+
+    fn print<W: Write>(&self, what: &[&str], out: &mut W) -> io::Result<()> {
+        let format = match what.first() {
+            None => SNAPSHOT,
+            Some(&"register") => "{register}",
+            Some(&"memory") => "{memory}",
+            Some(&"inbox") => "{inbox}",
+            Some(&"outbox") => "{outbox}",
+            Some(&"counter") => "{counter}",
+            Some(&"cell") => {
+                return match parse_cell(what.get(1).cloned()) {
+                    Ok(cell) => writeln!(out, "{}", format_snapshot(&format!("{{cell:{}}}", cell), self.iterator.state)),
+                    Err(reason) => writeln!(out, "{}", reason)
+                };
+            },
+            Some(other) => return writeln!(out, "cannot print {}", other)
+        };
+
+        writeln!(out, "{}", format_snapshot(format, self.iterator.state))
+    }
+
+    fn list<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let hooks = &self.iterator.hooks;
+        for index in 0..self.iterator.operations.len() {
+            let marker = if index == self.iterator.state.instruction_counter { "=>" } else { "  " };
+            let has_breakpoint = hooks.breakpoints().iter().any(|pair| {
+                pair.1.position.as_ref().and_then(|position| hooks.resolve(position)) == Some(index)
+            });
+            writeln!(out, "{}{} {}", marker, if has_breakpoint { "*" } else { " " }, self.describe_instruction(index))?;
+        }
+        Ok(())
+    }
+
+    fn set(&mut self, tokens: &[&str]) -> Result<(), String> {
+        let state = &mut self.iterator.state;
+        match tokens.first() {
+            Some(&"register") if tokens.len() == 2 => {
+                state.register = parse_tile(tokens[1])?;
+                Ok(())
+            },
+            Some(&"cell") if tokens.len() == 3 => {
+                let cell = parse_cell(Some(tokens[1]))?;
+                if cell >= state.floor_size() {
+                    return Err(format!("cell {} is not on the floor", cell));
+                }
+                state.memory[cell] = parse_tile(tokens[2])?;
+                Ok(())
+            },
+            _ => Err(String::from("usage: set register <value> or set cell N <value>"))
+        }
+    }
+
+    fn add_breakpoint(&mut self, tokens: &[&str]) -> Result<usize, String> {
+        let breakpoint = if tokens.first() == Some(&"if") {
+            Breakpoint::on_condition(parse_condition(&tokens[1..])?)
+        }
+        else {
+            let position = self.parse_position(tokens.first())?;
+            let breakpoint = Breakpoint{position: Some(position), condition: None};
+            match tokens.get(1) {
+                Some(&"if") => breakpoint.when(parse_condition(&tokens[2..])?),
+                Some(other) => return Err(format!("unexpected {}", other)),
+                None => breakpoint
+            }
+        };
+
+        Ok(self.iterator.hooks.add_breakpoint(breakpoint))
+    }
+
+    fn info<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        for &(id, ref breakpoint) in self.iterator.hooks.breakpoints() {
+            writeln!(out, "breakpoint {}: {:?} {:?}", id, breakpoint.position, breakpoint.condition)?;
+        }
+        for &(id, ref watchpoint) in self.iterator.hooks.watchpoints() {
+            writeln!(out, "watchpoint {}: {:?} {:?}", id, watchpoint.target, watchpoint.kind)?;
+        }
+        for &(id, ref tracepoint) in self.iterator.hooks.tracepoints() {
+            writeln!(out, "tracepoint {}: {:?} {:?}", id, tracepoint.position, tracepoint.format)?;
+        }
+        Ok(())
+    }
+
+    // runs a single command; returns false once the user wants to quit
+    pub fn execute<W: Write>(&mut self, line: &str, out: &mut W) -> io::Result<bool> {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let command = match tokens.first() {
+            Some(command) => *command,
+            None => return Ok(true)
+        };
+        let arguments = &tokens[1..];
+
+        match command {
+            "step" | "s" => {
+                let mut stop = Stop::Executed;
+                match parse_steps(arguments.first()) {
+                    Ok(steps) => for _ in 0..steps {
+                        stop = self.step();
+                        if let Stop::Executed = stop { continue; }
+                        break;
+                    },
+                    Err(reason) => return writeln!(out, "{}", reason).map(|_| true)
+                }
+                self.report(stop, out)?;
+            },
+            "next" | "n" => {
+                let mut stop = Stop::Executed;
+                match parse_steps(arguments.first()) {
+                    Ok(steps) => for _ in 0..steps {
+                        stop = self.next_instruction();
+                        if let Stop::Executed = stop { continue; }
+                        break;
+                    },
+                    Err(reason) => return writeln!(out, "{}", reason).map(|_| true)
+                }
+                self.report(stop, out)?;
+            },
+            "continue" | "c" => {
+                let mut stop = self.advance();
+                while let Stop::Executed = stop {
+                    stop = self.advance();
+                }
+                self.report(stop, out)?;
+            },
+            "until" | "u" => match self.parse_position(arguments.first()) {
+                Ok(position) => {
+                    let id = self.iterator.hooks.add_breakpoint(Breakpoint{position: Some(position), condition: None});
+                    let mut stop = self.step();
+                    while let Stop::Executed = stop {
+                        stop = self.advance();
+                    }
+                    self.iterator.hooks.remove_breakpoint(id);
+                    // reaching the position is not worth a message
+                    if stop == Stop::Paused(Hit::Breakpoint{id, instruction: self.iterator.state.instruction_counter}) {
+                        stop = Stop::Executed;
+                    }
+                    self.report(stop, out)?;
+                },
+                Err(reason) => writeln!(out, "{}", reason)?
+            },
+            "back" | "k" => {
+                let mut result = Ok(());
+                match parse_steps(arguments.first()) {
+                    Ok(steps) => for _ in 0..steps {
+                        result = self.step_back();
+                        if result.is_err() { break; }
+                    },
+                    Err(reason) => return writeln!(out, "{}", reason).map(|_| true)
+                }
+                if let Err(reason) = result {
+                    writeln!(out, "{}", reason)?;
+                }
+                self.report(Stop::Executed, out)?;
+            },
+            "goto" | "g" => match arguments.first().and_then(|step| step.parse::<u32>().ok()) {
+                Some(step) => {
+                    let stop = self.goto(step);
+                    self.report(stop, out)?;
+                },
+                None => writeln!(out, "usage: goto <step>")?
+            },
+            "changed" => self.last_change(arguments, out)?,
+            "print" | "p" => self.print(arguments, out)?,
+            "list" | "l" => self.list(out)?,
+            "set" => if let Err(reason) = self.set(arguments) {
+                writeln!(out, "{}", reason)?;
+            },
+            "break" | "b" => match self.add_breakpoint(arguments) {
+                Ok(id) => writeln!(out, "breakpoint {} added", id)?,
+                Err(reason) => writeln!(out, "{}", reason)?
+            },
+            "watch" | "w" | "rwatch" => {
+                let access = if command == "rwatch" { Access::Read } else { Access::Write };
+                match parse_watchpoint(arguments, access) {
+                    Ok(watchpoint) => {
+                        let id = self.iterator.hooks.add_watchpoint(watchpoint);
+                        writeln!(out, "watchpoint {} added", id)?;
+                    },
+                    Err(reason) => writeln!(out, "{}", reason)?
+                }
+            },
+            "trace" | "t" => match self.parse_position(arguments.first()) {
+                Ok(position) if arguments.len() > 1 => {
+                    let format = arguments[1..].join(" ");
+                    let id = self.iterator.hooks.add_tracepoint(Tracepoint::new(position, &format));
+                    writeln!(out, "tracepoint {} added", id)?;
+                },
+                Ok(_) => writeln!(out, "missing format")?,
+                Err(reason) => writeln!(out, "{}", reason)?
+            },
+            "unbreak" | "delete" | "d" => match arguments.first().and_then(|id| id.parse::<usize>().ok()) {
+                Some(id) => {
+                    let removed = self.iterator.hooks.remove_breakpoint(id)
+                        || self.iterator.hooks.remove_watchpoint(id)
+                        || self.iterator.hooks.remove_tracepoint(id);
+                    if !removed {
+                        writeln!(out, "no breakpoint {}", id)?;
+                    }
+                },
+                None => writeln!(out, "usage: unbreak <id>")?
+            },
+            "info" | "i" => self.info(out)?,
+            "help" | "h" => writeln!(out, "{}", HELP)?,
+            "quit" | "q" => return Ok(false),
+            _ => writeln!(out, "unknown command {}, try help", command)?
+        }
+
+        Ok(true)
+    }
+
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, out: &mut W) -> io::Result<()> {
+        let counter = self.iterator.state.instruction_counter;
+        writeln!(out, "=> {}", self.describe_instruction(counter))?;
+
+        let mut lines = input.lines();
+        loop {
+            write!(out, "(hrm) ")?;
+            out.flush()?;
+            let line = match lines.next() {
+                Some(line) => line?,
+                None => return Ok(())
+            };
+            if !self.execute(&line, out)? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use Value;
+    use Operation;
+    use Location;
+    use CodeIterator;
+    use debugger::Debugger;
+    use state::InternalState;
+
+    fn program() -> Vec<Operation> {
+        vec!(
+            Operation::Label,
+            Operation::Inbox,
+            Operation::CopyTo{cell: Location::Cell(0)},
+            Operation::Outbox,
+            Operation::Jump{next_operation: 0}
+        )
+    }
+
+    fn state() -> InternalState {
+        InternalState::new(None, 0)
+            .with_input_tape(vec!(Value::Number{value: 1}, Value::Number{value: 2}))
+            .with_memory(vec!(None))
+    }
+
+    fn run_commands(debugger: &mut Debugger, commands: &[&str]) -> String {
+        let mut out: Vec<u8> = vec!();
+        for command in commands {
+            debugger.execute(command, &mut out).unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn step_and_print() {
+        let mut state = state();
+        let iterator = CodeIterator::without_dump(&mut state, program());
+        let mut debugger = Debugger::new(iterator);
+
+        let out = run_commands(&mut debugger, &["step 2", "print register"]);
+
+        assert_eq!(out, "=> 2: CopyTo { cell: Cell(0) }\n2\n");
+    }
+
+    #[test]
+    fn next_skips_labels() {
+        let mut state = state();
+        let iterator = CodeIterator::without_dump(&mut state, program());
+        let mut debugger = Debugger::new(iterator);
+
+        run_commands(&mut debugger, &["next 4"]);
+
+        assert_eq!(debugger.iterator.state.instruction_counter, 1);
+        assert_eq!(debugger.iterator.state.output_tape, vec!(Value::Number{value: 2}));
+    }
+
+    #[test]
+    fn break_on_label_and_continue() {
+        let mut state = state();
+        let iterator = CodeIterator::without_dump(&mut state, program())
+            .with_labels(vec!((String::from("start"), 0)));
+        let mut debugger = Debugger::new(iterator);
+
+        let out = run_commands(&mut debugger, &["break start", "continue", "continue", "unbreak 1", "continue"]);
+
+        assert_eq!(out, "breakpoint 1 added\n\
+            breakpoint 1 hit at instruction 0\n=> 0: Label (start:)\n\
+            breakpoint 1 hit at instruction 0\n=> 0: Label (start:)\n\
+            program finished: inbox empty\n");
+        assert!(debugger.is_finished());
+        assert_eq!(debugger.iterator.state.output_tape, vec!(Value::Number{value: 2}, Value::Number{value: 1}));
+    }
+
+    #[test]
+    fn finished_then_not_running() {
+        let mut state = state();
+        let iterator = CodeIterator::without_dump(&mut state, vec!(Operation::Inbox, Operation::Outbox));
+        let mut debugger = Debugger::new(iterator);
+
+        let out = run_commands(&mut debugger, &["continue", "step"]);
+
+        assert_eq!(out, "program finished: past the last instruction\n\
+            the program is not running\n");
+    }
+
+    #[test]
+    fn conditional_break_and_set() {
+        let mut state = state();
+        let iterator = CodeIterator::without_dump(&mut state, program());
+        let mut debugger = Debugger::new(iterator);
+
+        run_commands(&mut debugger, &["break 3 if cell 0 == 1", "continue", "set register a", "step"]);
+
+        assert_eq!(debugger.iterator.state.output_tape, vec!(Value::Number{value: 2}, Value::Character{value: 'a'}));
+    }
+
+    #[test]
+    fn until_and_trace() {
+        let mut state = state();
+        let iterator = CodeIterator::without_dump(&mut state, program());
+        let mut debugger = Debugger::new(iterator);
+
+        let out = run_commands(&mut debugger, &["trace 2 got {register}", "until 3"]);
+
+        assert_eq!(out, "tracepoint 1 added\ntrace: got 2\n=> 3: Outbox\n");
+    }
+
+    #[test]
+    fn invalid_commands() {
+        let mut state = state();
+        let iterator = CodeIterator::without_dump(&mut state, program());
+        let mut debugger = Debugger::new(iterator);
+
+        let out = run_commands(&mut debugger, &["break nowhere", "set cell 4 1", "jump"]);
+
+        assert_eq!(out, "nowhere is not in the program\n\
+            cell 4 is not on the floor\n\
+            unknown command jump, try help\n");
+    }
+
+    #[test]
+    fn back_goto_and_changed() {
+        let mut state = state();
+        let iterator = CodeIterator::without_dump(&mut state, program()).with_history();
+        let mut debugger = Debugger::new(iterator);
+
+        let out = run_commands(&mut debugger, &["continue", "changed cell 0", "back 4", "print outbox", "goto 3", "print cell 0"]);
+
+        assert_eq!(out, "program finished: inbox empty\n\
+            cell 0 changed at step 7 by 2: CopyTo { cell: Cell(0) }\n\
+            => 2: CopyTo { cell: Cell(0) }\n\
+            [2]\n\
+            => 3: Outbox\n\
+            2\n");
+    }
+
+    #[test]
+    fn watchpoints_hit_together() {
+        let mut state = state();
+        let iterator = CodeIterator::without_dump(&mut state, program());
+        let mut debugger = Debugger::new(iterator);
+
+        let out = run_commands(&mut debugger, &["watch cell 0", "rwatch register", "until 2", "step"]);
+
+        assert_eq!(out, "watchpoint 1 added\n\
+            watchpoint 2 added\n\
+            => 2: CopyTo { cell: Cell(0) }\n\
+            watchpoint 1: cell 0 written by 2: CopyTo { cell: Cell(0) }: _ -> 2\n\
+            watchpoint 2: register read by 2: CopyTo { cell: Cell(0) }: 2\n\
+            => 3: Outbox\n");
+    }
+
+    #[test]
+    fn watch_and_step() {
+        let mut state = state();
+        let iterator = CodeIterator::without_dump(&mut state, program());
+        let mut debugger = Debugger::new(iterator);
+
+        let out = run_commands(&mut debugger, &["watch cell 0", "rwatch floor", "step 3", "continue"]);
+
+        assert_eq!(out, "watchpoint 1 added\n\
+            watch register, cell N or pointer N\n\
+            watchpoint 1: cell 0 written by 2: CopyTo { cell: Cell(0) }: _ -> 2\n\
+            => 3: Outbox\n\
+            watchpoint 1: cell 0 written by 2: CopyTo { cell: Cell(0) }: 2 -> 1\n\
+            => 3: Outbox\n");
+    }
+}
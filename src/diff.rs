@@ -0,0 +1,224 @@
+use std::fmt;
+
+use Value;
+use describe_value;
+use Operation;
+use RunOutcome;
+use level::first_mismatch;
+use limits::Limits;
+use score::scored_run;
+use state::InternalState;
+use suite::TestCase;
+
+// runs a program and an optimized version of it on the same inputs, checking
+// they fill the outbox with the same items and end the same way.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunSummary {
+    pub outbox: Vec<Value>,
+    pub outcome: RunOutcome,
+    // executed instructions, labels excluded
+    pub steps: u32
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Divergence {
+    // a None is an item only one of the programs put in the outbox
+    Outbox{position: usize, original: Option<Value>, optimized: Option<Value>},
+    Outcome{original: RunOutcome, optimized: RunOutcome}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub name: String,
+    pub original: RunSummary,
+    pub optimized: RunSummary,
+    pub divergence: Option<Divergence>
+}
+
+impl Comparison {
+    pub fn is_equivalent(&self) -> bool {
+        self.divergence.is_none()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffReport {
+    pub comparisons: Vec<Comparison>
+}
+
+impl DiffReport {
+    pub fn is_equivalent(&self) -> bool {
+        self.comparisons.iter().all(|comparison| comparison.is_equivalent())
+    }
+
+    // the first input the programs disagree on
+    pub fn first_divergence(&self) -> Option<&Comparison> {
+        self.comparisons.iter().find(|comparison| !comparison.is_equivalent())
+    }
+}
+
+// errors are the same if they have the same kind, wherever they happen in each program
+pub fn same_ending(original: &RunOutcome, optimized: &RunOutcome) -> bool {
+    match (original, optimized) {
+        (RunOutcome::RuntimeError(original), RunOutcome::RuntimeError(optimized)) |
+        (RunOutcome::LimitExceeded(original), RunOutcome::LimitExceeded(optimized)) =>
+            original.kind == optimized.kind,
+        (RunOutcome::Breakpoint(_), RunOutcome::Breakpoint(_)) => true,
+        _ => original == optimized
+    }
+}
+
+fn summarize(operations: &[Operation], mut state: InternalState, limits: Limits) -> RunSummary {
+    // the programs are compared with each other, not with the expected output
+    state.expected_output = None;
+    let run = scored_run(operations, &mut state, limits);
+    RunSummary{outbox: state.output_tape, outcome: run.outcome, steps: run.steps}
+}
+
+pub fn compare(name: &str, original: &[Operation], optimized: &[Operation], state: &InternalState, limits: Limits) -> Comparison {
+    let original = summarize(original, state.clone(), limits);
+    let optimized = summarize(optimized, state.clone(), limits);
+
+    let divergence = match first_mismatch(&original.outbox, &optimized.outbox) {
+        Some((position, original, optimized)) => Some(Divergence::Outbox{position, original, optimized}),
+        None if !same_ending(&original.outcome, &optimized.outcome) =>
+            Some(Divergence::Outcome{original: original.outcome.clone(), optimized: optimized.outcome.clone()}),
+        None => None
+    };
+
+    Comparison{name: String::from(name), original, optimized, divergence}
+}
+
+pub fn compare_cases(original: &[Operation], optimized: &[Operation], cases: &[TestCase], limits: Limits) -> DiffReport {
+    let comparisons = cases.iter()
+        .map(|case| compare(&case.name, original, optimized, &case.state, limits))
+        .collect();
+
+    DiffReport{comparisons}
+}
+
+pub fn describe_outcome(outcome: &RunOutcome) -> String {
+    match *outcome {
+        RunOutcome::CompletedOnEmptyInbox => String::from("the empty inbox"),
+        RunOutcome::CompletedAtEnd => String::from("the end of the program"),
+        RunOutcome::RuntimeError(ref error) | RunOutcome::LimitExceeded(ref error) => format!("the error '{}'", error.kind),
+        RunOutcome::Breakpoint(_) => String::from("a breakpoint")
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Divergence::Outbox{position, original, optimized} =>
+                write!(f, "outbox item {} is {} in the original, {} in the optimized program",
+                    position, describe_value(original, "nothing"), describe_value(optimized, "nothing")),
+            Divergence::Outcome{ref original, ref optimized} =>
+                write!(f, "the original stops at {}, the optimized program at {}",
+                    describe_outcome(original), describe_outcome(optimized))
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.divergence {
+            Some(ref divergence) => write!(f, "{}: {}", self.name, divergence)?,
+            None => write!(f, "{}: equivalent", self.name)?
+        }
+        write!(f, " ({} -> {} steps)", self.original.steps, self.optimized.steps)
+    }
+}
+
+impl fmt::Display for DiffReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for comparison in &self.comparisons {
+            writeln!(f, "{}", comparison)?;
+        }
+        let equivalent = self.comparisons.iter().filter(|comparison| comparison.is_equivalent()).count();
+        let original: u64 = self.comparisons.iter().map(|comparison| comparison.original.steps as u64).sum();
+        let optimized: u64 = self.comparisons.iter().map(|comparison| comparison.optimized.steps as u64).sum();
+        writeln!(f, "{} of {} inputs equivalent, {} -> {} steps in total", equivalent, self.comparisons.len(), original, optimized)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use Value;
+    use Location;
+    use Operation;
+    use RunOutcome;
+    use limits::Limits;
+    use state::InternalState;
+    use suite::TestCase;
+    use diff::{Divergence, compare, compare_cases};
+
+    // copies the inbox to the outbox, going through the floor
+    fn original() -> Vec<Operation> {
+        vec!(
+            Operation::Label,
+            Operation::Inbox,
+            Operation::CopyTo{cell: Location::Cell(0)},
+            Operation::CopyFrom{cell: Location::Cell(0)},
+            Operation::Outbox,
+            Operation::Jump{next_operation: 0}
+        )
+    }
+
+    fn optimized() -> Vec<Operation> {
+        vec!(Operation::Label, Operation::Inbox, Operation::Outbox, Operation::Jump{next_operation: 0})
+    }
+
+    fn state(inbox: Vec<i32>) -> InternalState {
+        InternalState::new(None, 0)
+            .with_input_tape(inbox.into_iter().rev().map(|value| Value::Number{value}).collect())
+            .with_memory(vec!(None))
+    }
+
+    #[test]
+    fn equivalent_programs() {
+        let cases = vec!(
+            TestCase{name: String::from("two"), state: state(vec!(1, 2))},
+            TestCase{name: String::from("none"), state: state(vec!())}
+        );
+        let report = compare_cases(&original(), &optimized(), &cases, Limits::default());
+
+        assert!(report.is_equivalent());
+        assert_eq!(report.first_divergence(), None);
+        assert_eq!(report.to_string(), "two: equivalent (10 -> 6 steps)\n\
+            none: equivalent (0 -> 0 steps)\n\
+            2 of 2 inputs equivalent, 10 -> 6 steps in total\n");
+    }
+
+    #[test]
+    fn diverging_outbox() {
+        let mut broken = optimized();
+        broken.insert(2, Operation::BumpPlus{cell: Location::Cell(0)});
+        let comparison = compare("bump", &original(), &broken, &state(vec!(1, 2)), Limits::default());
+
+        // the first item goes out before the bump fails on the empty cell
+        assert!(!comparison.is_equivalent());
+        assert!(match comparison.divergence {
+            Some(Divergence::Outbox{position: 0, original: Some(Value::Number{value: 1}), optimized: None}) => true,
+            _ => false
+        });
+    }
+
+    #[test]
+    fn diverging_outcome() {
+        let ends_early = vec!(Operation::Inbox, Operation::Outbox);
+        let comparison = compare("one", &original(), &ends_early, &state(vec!(5)), Limits::default());
+
+        assert_eq!(comparison.divergence,
+            Some(Divergence::Outcome{original: RunOutcome::CompletedOnEmptyInbox, optimized: RunOutcome::CompletedAtEnd}));
+        assert_eq!(comparison.to_string(),
+            "one: the original stops at the empty inbox, the optimized program at the end of the program (5 -> 2 steps)");
+    }
+
+    #[test]
+    fn expected_output_is_ignored() {
+        let checked = state(vec!(1)).with_expected_output(Some(vec!(Value::Number{value: 7})));
+
+        assert!(compare("checked", &original(), &optimized(), &checked, Limits::default()).is_equivalent());
+    }
+}
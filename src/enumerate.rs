@@ -0,0 +1,205 @@
+use Value;
+use parse_value;
+use describe_values;
+use Operation;
+use diff::{Comparison, compare};
+use limits::Limits;
+use state::InternalState;
+
+// every inbox up to some length over a few values, to compare two programs
+// on all the small inputs instead of a handful of chosen ones.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Domain {
+    // the order of the values decides which counterexample is reported first
+    pub values: Vec<Value>
+}
+
+impl Domain {
+    pub fn new(values: Vec<Value>) -> Self {
+        Domain{values}
+    }
+
+    // comma separated numbers, letters and inclusive ranges, like "-5..5,a,b"
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let mut values = vec!();
+        for item in raw.split(',').map(|item| item.trim()) {
+            match item.find("..").filter(|&start| start > 0) {
+                Some(start) => {
+                    let low = parse_number(&item[..start])?;
+                    let high = parse_number(item[start + 2..].trim_start_matches('='))?;
+                    if low > high {
+                        return Err(format!("the range {} is empty", item));
+                    }
+                    values.extend((low..=high).map(|value| Value::Number{value}));
+                },
+                None => values.push(parse_value(item)?)
+            }
+        }
+
+        let mut domain = vec!();
+        for value in values {
+            if !domain.contains(&value) {
+                domain.push(value);
+            }
+        }
+        Ok(Domain::new(domain))
+    }
+
+    // shortest inboxes first, then in the order of the values
+    pub fn inboxes(&self, max_length: usize) -> Inboxes<'_> {
+        Inboxes{domain: &self.values, max_length, next: Some(vec!())}
+    }
+}
+
+fn parse_number(raw: &str) -> Result<i32, String> {
+    raw.trim().parse::<i32>().map_err(|_| format!("{} is not a number", raw))
+}
+
+pub struct Inboxes<'d> {
+    domain: &'d [Value],
+    max_length: usize,
+    // positions in the domain of the items of the next inbox
+    next: Option<Vec<usize>>
+}
+
+impl<'d> Inboxes<'d> {
+    fn advance(&self, mut positions: Vec<usize>) -> Option<Vec<usize>> {
+        // counts like an odometer, the last item changing first
+        for position in positions.iter_mut().rev() {
+            *position += 1;
+            if *position < self.domain.len() {
+                return Some(positions);
+            }
+            *position = 0;
+        }
+
+        let length = positions.len() + 1;
+        if length > self.max_length || self.domain.is_empty() {
+            None
+        }
+        else {
+            Some(vec!(0; length))
+        }
+    }
+}
+
+impl<'d> Iterator for Inboxes<'d> {
+    type Item = Vec<Value>;
+
+    fn next(&mut self) -> Option<Vec<Value>> {
+        let positions = self.next.take()?;
+        let inbox = positions.iter().map(|&position| self.domain[position]).collect();
+        self.next = self.advance(positions);
+        Some(inbox)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Counterexample {
+    // in the order the items come in
+    pub inbox: Vec<Value>,
+    pub comparison: Comparison
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exhaustion {
+    // inboxes the programs ran on
+    pub tried: usize,
+    pub counterexample: Option<Counterexample>
+}
+
+// floor gives the memory, value range and semantics; its inbox is replaced by each
+// enumerated one. The first disagreement is a shortest one.
+pub fn find_counterexample(original: &[Operation], optimized: &[Operation], floor: &InternalState,
+        domain: &Domain, max_length: usize, limits: Limits) -> Exhaustion {
+    let mut tried = 0;
+    for inbox in domain.inboxes(max_length) {
+        tried += 1;
+        let mut state = floor.clone();
+        state.input_tape = inbox.iter().rev().cloned().collect();
+
+        let comparison = compare(&describe_values(&inbox), original, optimized, &state, limits);
+        if !comparison.is_equivalent() {
+            return Exhaustion{tried, counterexample: Some(Counterexample{inbox, comparison})};
+        }
+    }
+
+    Exhaustion{tried, counterexample: None}
+}
+
+#[cfg(test)]
+mod test {
+    use Value;
+    use Location;
+    use Operation;
+    use limits::Limits;
+    use state::InternalState;
+    use enumerate::{Domain, find_counterexample};
+
+    fn number(value: i32) -> Value {
+        Value::Number{value}
+    }
+
+    #[test]
+    fn parses_domains() {
+        assert_eq!(Domain::parse("-1..1, a, 0, 3..=4"),
+            Ok(Domain::new(vec!(number(-1), number(0), number(1), Value::Character{value: 'a'}, number(3), number(4)))));
+        assert!(Domain::parse("2..1").is_err());
+        assert!(Domain::parse("ab").is_err());
+    }
+
+    #[test]
+    fn shortest_inboxes_first() {
+        let domain = Domain::new(vec!(number(0), number(1)));
+        let inboxes: Vec<Vec<Value>> = domain.inboxes(2).collect();
+
+        assert_eq!(inboxes, vec!(
+            vec!(),
+            vec!(number(0)), vec!(number(1)),
+            vec!(number(0), number(0)), vec!(number(0), number(1)), vec!(number(1), number(0)), vec!(number(1), number(1))
+        ));
+        assert_eq!(Domain::new(vec!()).inboxes(3).count(), 1);
+    }
+
+    // outputs the inbox items that are zero
+    fn zeros() -> Vec<Operation> {
+        vec!(
+            Operation::Label,
+            Operation::Inbox,
+            Operation::JumpEqualsZero{next_operation: 4},
+            Operation::Jump{next_operation: 0},
+            Operation::Label,
+            Operation::Outbox,
+            Operation::Jump{next_operation: 0}
+        )
+    }
+
+    #[test]
+    fn minimal_counterexample() {
+        // outputs the negative items instead
+        let mut broken = zeros();
+        broken[2] = Operation::JumpNegative{next_operation: 4};
+        let domain = Domain::parse("1,-1,0").unwrap();
+        let exhaustion = find_counterexample(&zeros(), &broken, &InternalState::new(None, 0), &domain, 3, Limits::default());
+
+        let counterexample = exhaustion.counterexample.unwrap();
+        assert_eq!(counterexample.inbox, vec!(number(-1)));
+        assert_eq!(exhaustion.tried, 3);
+        assert_eq!(counterexample.comparison.name, "[-1]");
+    }
+
+    #[test]
+    fn equivalent_on_every_inbox() {
+        // goes through the floor, slower but the same
+        let mut same = zeros();
+        same.insert(2, Operation::CopyTo{cell: Location::Cell(0)});
+        same.insert(3, Operation::CopyFrom{cell: Location::Cell(0)});
+        same[4] = Operation::JumpEqualsZero{next_operation: 6};
+        let floor = InternalState::new(None, 0).with_memory(vec!(None));
+        let exhaustion = find_counterexample(&zeros(), &same, &floor, &Domain::parse("-1..1").unwrap(), 3, Limits::default());
+
+        assert_eq!(exhaustion.counterexample, None);
+        assert_eq!(exhaustion.tried, 1 + 3 + 9 + 27);
+    }
+}
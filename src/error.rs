@@ -0,0 +1,183 @@
+use std::fmt;
+
+use Value;
+use Operation;
+
+// every way an instruction can fail at runtime.
+// variants carry the cells and values involved, so that tools
+// can match on them instead of parsing error messages.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum ErrorKind {
+	EmptyInbox,
+	EmptyRegister,
+	EmptyCell{cell: usize},
+	PointerCellContainsChar{cell: usize, value: char},
+	CellOutOfRange{cell: usize, floor_size: usize},
+	PointerOutOfRange{cell: usize, pointer: i32, floor_size: usize},
+	CharArithmetic{register: Value, operand: Value},
+	NotALetter{value: char},
+	LetterOverflow{register: Value, operand: Value},
+	Overflow{value: i64},
+	BumpChar{cell: usize, value: char},
+	CharComparedToZero{value: char},
+	// expected is None when the program puts too many items in the outbox
+	WrongOutput{position: usize, expected: Option<Value>, actual: Value},
+	InstructionsLimitReached{limit: u32},
+	OutputLimitReached{limit: usize},
+	TimeoutReached{milliseconds: u64},
+	HistoryLimitReached{limit: usize}
+}
+
+impl ErrorKind {
+	// errors stopping programs that went on for too long rather than failing
+	pub fn is_limit(&self) -> bool {
+		matches!(*self,
+			ErrorKind::InstructionsLimitReached{..} | ErrorKind::OutputLimitReached{..} |
+			ErrorKind::TimeoutReached{..} | ErrorKind::HistoryLimitReached{..})
+	}
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct RuntimeError {
+	pub instruction: usize,
+	pub operation: Operation,
+	pub kind: ErrorKind
+}
+
+impl RuntimeError {
+	pub fn new(instruction: usize, operation: Operation, kind: ErrorKind) -> Self {
+		RuntimeError{instruction, operation, kind}
+	}
+}
+
+fn describe(value: &Value) -> String {
+	match *value {
+		Value::Number{value: number} => format!("{}", number),
+		Value::Character{value: character} => format!("{:?}", character)
+	}
+}
+
+impl fmt::Display for ErrorKind {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ErrorKind::EmptyInbox =>
+				write!(f, "The inbox is empty, you cannot pick a new value from it!"),
+			ErrorKind::EmptyRegister =>
+				write!(f, "the Employee register holds no value"),
+			ErrorKind::EmptyCell{cell} =>
+				write!(f, "There is no value at cell {}", cell),
+			ErrorKind::PointerCellContainsChar{cell, value} =>
+				write!(f, "Cell {} should contain a number, not a char({:?})", cell, value),
+			ErrorKind::CellOutOfRange{cell, floor_size} =>
+				write!(f, "cell {} is not on the floor, which has {} tiles", cell, floor_size),
+			ErrorKind::PointerOutOfRange{cell, pointer, floor_size} if pointer < 0 =>
+				write!(f, "cell {} holds a negative address ({}), floor has {} tiles", cell, pointer, floor_size),
+			ErrorKind::PointerOutOfRange{cell, pointer, floor_size} =>
+				write!(f, "cell {} points to {}, which is not on the floor ({} tiles)", cell, pointer, floor_size),
+			ErrorKind::CharArithmetic{register, operand} =>
+				write!(f, "cannot perform arithmetic between {} and {}", describe(&register), describe(&operand)),
+			ErrorKind::NotALetter{value} =>
+				write!(f, "{:?} is not a letter", value),
+			ErrorKind::LetterOverflow{register, operand} =>
+				write!(f, "value overflowed! {} and {} do not give a representable letter!", describe(&register), describe(&operand)),
+			ErrorKind::Overflow{value} =>
+				write!(f, "value overflowed! {} is out of the allowed range", value),
+			ErrorKind::BumpChar{cell, value} =>
+				write!(f, "Cannot bump char {:?} at cell {}", value, cell),
+			ErrorKind::WrongOutput{position, expected: Some(expected), actual} =>
+				write!(f, "outbox item {} should be {}, got {}", position, expected, actual),
+			ErrorKind::WrongOutput{position, expected: None, actual} =>
+				write!(f, "outbox item {} should not be there, got {}", position, actual),
+			ErrorKind::CharComparedToZero{value} =>
+				write!(f, "cannot compare a character ({:?}) to zero!", value),
+			ErrorKind::InstructionsLimitReached{limit} =>
+				write!(f, "instructions limit reached ({} instructions)", limit),
+			ErrorKind::OutputLimitReached{limit} =>
+				write!(f, "output limit reached ({} items)", limit),
+			ErrorKind::TimeoutReached{milliseconds} =>
+				write!(f, "timeout reached ({} ms)", milliseconds),
+			ErrorKind::HistoryLimitReached{limit} =>
+				write!(f, "history limit reached ({} instructions)", limit)
+		}
+	}
+}
+
+impl fmt::Display for RuntimeError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "instruction {} ({:?}): {}", self.instruction, self.operation, self.kind)
+	}
+}
+
+// problems found while loading programs and input files
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadErrorKind {
+	Io{reason: String},
+	Syntax{reason: String},
+	UnknownOperation{operation: String},
+	MissingOperand{operation: String},
+	InvalidOperand{operation: String, operand: String},
+	UnknownLabel{label: String},
+	// the floor cannot hold all the tiles given in memory
+	FloorTooSmall{floor_size: usize, tiles: usize}
+}
+
+// instruction is the index in the loaded program, while line and column
+// point to the place in the source file, whenever they are known.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadError {
+	pub kind: LoadErrorKind,
+	pub instruction: Option<usize>,
+	pub line: Option<usize>,
+	pub column: Option<usize>
+}
+
+impl LoadError {
+	pub fn new(kind: LoadErrorKind) -> Self {
+		LoadError{kind, instruction: None, line: None, column: None}
+	}
+
+	pub fn at_instruction(mut self, instruction: usize) -> Self {
+		self.instruction = Some(instruction);
+		self
+	}
+
+	pub fn at_position(mut self, line: usize, column: usize) -> Self {
+		self.line = Some(line);
+		self.column = Some(column);
+		self
+	}
+}
+
+impl fmt::Display for LoadErrorKind {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			LoadErrorKind::Io{ref reason} =>
+				write!(f, "could not read the file: {}", reason),
+			LoadErrorKind::Syntax{ref reason} =>
+				write!(f, "{}", reason),
+			LoadErrorKind::UnknownOperation{ref operation} =>
+				write!(f, "unrecognized operation {}", operation),
+			LoadErrorKind::MissingOperand{ref operation} =>
+				write!(f, "'{}' needs an operand", operation),
+			LoadErrorKind::InvalidOperand{ref operation, ref operand} =>
+				write!(f, "{} is not a valid operand for '{}'", operand, operation),
+			LoadErrorKind::UnknownLabel{ref label} =>
+				write!(f, "label {} is never defined", label),
+			LoadErrorKind::FloorTooSmall{floor_size, tiles} =>
+				write!(f, "floor_size {} is smaller than the {} tiles given in memory", floor_size, tiles)
+		}
+	}
+}
+
+// formatted like compiler diagnostics: "line:column: instruction N: reason"
+impl fmt::Display for LoadError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		if let (Some(line), Some(column)) = (self.line, self.column) {
+			write!(f, "{}:{}: ", line, column)?;
+		}
+		if let Some(instruction) = self.instruction {
+			write!(f, "instruction {}: ", instruction)?;
+		}
+		write!(f, "{}", self.kind)
+	}
+}
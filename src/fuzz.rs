@@ -0,0 +1,432 @@
+use std::fmt;
+
+use Value;
+use describe_values;
+use Operation;
+use CodeIterator;
+use RunOutcome;
+use diff::compare;
+use limits::Limits;
+use state::InternalState;
+
+// random inboxes shaped by a generator, to look for inputs breaking a program,
+// and shrinking to a smaller inbox breaking it the same way.
+
+// a value of the inbox
+#[derive(Debug, Clone, PartialEq)]
+pub enum Item {
+    Number{min: i32, max: i32},
+    Letter{letters: Vec<char>}
+}
+
+// counts are inclusive ranges, like (min, max)
+#[derive(Debug, Clone, PartialEq)]
+pub enum Generator {
+    // count items
+    Items{item: Item, count: (usize, usize)},
+    // count strings of letters, each followed by a zero
+    Strings{letters: Vec<char>, length: (usize, usize), count: (usize, usize)},
+    // count items of first, each followed by an item of second
+    Pairs{first: Item, second: Item, count: (usize, usize)}
+}
+
+// xorshift64*, good enough to pick inputs and the same on every platform
+#[derive(Debug, Clone, PartialEq)]
+pub struct Random {
+    state: u64
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        // xorshift never leaves zero
+        Random{state: if seed == 0 { 0x9e37_79b9_7f4a_7c15 } else { seed }}
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    // between min and max, both included
+    pub fn between(&mut self, min: i64, max: i64) -> i64 {
+        let span = (max - min) as u64 + 1;
+        min + (self.next_u64() % span) as i64
+    }
+
+    fn count(&mut self, (min, max): (usize, usize)) -> usize {
+        self.between(min as i64, max as i64) as usize
+    }
+}
+
+impl Item {
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            Item::Number{min, max} if min > max => Err(format!("the range {}..{} is empty", min, max)),
+            Item::Letter{ref letters} => validate_letters(letters),
+            _ => Ok(())
+        }
+    }
+
+    fn generate(&self, random: &mut Random) -> Value {
+        match *self {
+            Item::Number{min, max} => Value::Number{value: random.between(min as i64, max as i64) as i32},
+            Item::Letter{ref letters} => Value::Character{value: letters[random.count((0, letters.len() - 1))]}
+        }
+    }
+
+    // simpler values first: closer to zero, or earlier in the letters
+    fn simpler(&self, value: Value) -> Vec<Value> {
+        match (self, value) {
+            (&Item::Number{min, max}, Value::Number{value}) => {
+                // the target, then halving the distance to it
+                let target = 0.max(min).min(max);
+                let mut distance = value - target;
+                let mut simpler = vec!();
+                while distance != 0 {
+                    simpler.push(Value::Number{value: value - distance});
+                    distance /= 2;
+                }
+                simpler
+            },
+            (Item::Letter{letters}, Value::Character{value}) => simpler_letters(letters, value),
+            _ => vec!()
+        }
+    }
+}
+
+fn validate_letters(letters: &[char]) -> Result<(), String> {
+    if letters.is_empty() {
+        Err(String::from("there are no letters to pick from"))
+    }
+    else {
+        Ok(())
+    }
+}
+
+fn simpler_letters(letters: &[char], value: char) -> Vec<Value> {
+    let index = letters.iter().position(|&letter| letter == value).unwrap_or(0);
+    letters[..index].iter().map(|&letter| Value::Character{value: letter}).collect()
+}
+
+fn validate_range(name: &str, (min, max): (usize, usize)) -> Result<(), String> {
+    if min > max {
+        Err(format!("the {} range {}..{} is empty", name, min, max))
+    }
+    else {
+        Ok(())
+    }
+}
+
+fn flatten(groups: &[Vec<Value>]) -> Vec<Value> {
+    groups.iter().flat_map(|group| group.iter().cloned()).collect()
+}
+
+impl Generator {
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            Generator::Items{ref item, count} => item.validate().and(validate_range("count", count)),
+            Generator::Strings{ref letters, length, count} =>
+                validate_letters(letters).and(validate_range("length", length)).and(validate_range("count", count)),
+            Generator::Pairs{ref first, ref second, count} =>
+                first.validate().and(second.validate()).and(validate_range("count", count))
+        }
+    }
+
+    // the inbox split in its items, strings or pairs
+    pub fn generate(&self, random: &mut Random) -> Vec<Vec<Value>> {
+        match *self {
+            Generator::Items{ref item, count} => (0..random.count(count))
+                .map(|_| vec!(item.generate(random)))
+                .collect(),
+            Generator::Strings{ref letters, length, count} => (0..random.count(count))
+                .map(|_| {
+                    let letter = Item::Letter{letters: letters.clone()};
+                    let mut string: Vec<Value> = (0..random.count(length)).map(|_| letter.generate(random)).collect();
+                    string.push(Value::Number{value: 0});
+                    string
+                })
+                .collect(),
+            Generator::Pairs{ref first, ref second, count} => (0..random.count(count))
+                .map(|_| vec!(first.generate(random), second.generate(random)))
+                .collect()
+        }
+    }
+
+    fn count(&self) -> (usize, usize) {
+        match *self {
+            Generator::Items{count, ..} | Generator::Strings{count, ..} | Generator::Pairs{count, ..} => count
+        }
+    }
+
+    // the simpler values of the item at some position of a group
+    fn simpler(&self, value: Value, position: usize) -> Vec<Value> {
+        match *self {
+            Generator::Items{ref item, ..} => item.simpler(value),
+            Generator::Strings{ref letters, ..} => match value {
+                Value::Character{value} => simpler_letters(letters, value),
+                // the terminating zero
+                Value::Number{..} => vec!()
+            },
+            Generator::Pairs{ref first, ref second, ..} => if position == 0 { first.simpler(value) } else { second.simpler(value) }
+        }
+    }
+
+    // smaller inboxes the generator could also have made: fewer groups,
+    // shorter strings, then simpler values
+    pub fn shrink(&self, groups: &[Vec<Value>]) -> Vec<Vec<Vec<Value>>> {
+        let mut candidates = vec!();
+        if groups.len() > self.count().0 {
+            for index in 0..groups.len() {
+                let mut smaller = groups.to_vec();
+                smaller.remove(index);
+                candidates.push(smaller);
+            }
+        }
+        if let Generator::Strings{length: (min_length, _), ..} = *self {
+            for (index, string) in groups.iter().enumerate() {
+                // the last item is the zero
+                for position in 0..string.len() - 1 {
+                    if string.len() - 1 > min_length {
+                        let mut smaller = groups.to_vec();
+                        smaller[index].remove(position);
+                        candidates.push(smaller);
+                    }
+                }
+            }
+        }
+        for (index, group) in groups.iter().enumerate() {
+            for (position, value) in group.iter().enumerate() {
+                for simpler_value in self.simpler(*value, position) {
+                    let mut simpler = groups.to_vec();
+                    simpler[index][position] = simpler_value;
+                    candidates.push(simpler);
+                }
+            }
+        }
+        candidates
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Failure {
+    // the random inbox that failed first, in the order the items come in
+    pub inbox: Vec<Value>,
+    pub shrunk: Vec<Value>,
+    // why the shrunk inbox fails
+    pub reason: String,
+    // smaller inboxes that still failed on the way
+    pub shrinks: usize
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzReport {
+    pub seed: u64,
+    // inboxes tried before the first failure, that one included
+    pub runs: usize,
+    pub failure: Option<Failure>
+}
+
+// checks of the shrinking phase, whether they fail or not
+const MAX_SHRINK_ATTEMPTS: usize = 10000;
+
+// fails returns why an inbox breaks the program, if it does
+pub fn fuzz<F>(generator: &Generator, runs: usize, seed: u64, mut fails: F) -> FuzzReport
+        where F: FnMut(&[Value]) -> Option<String> {
+    let mut random = Random::new(seed);
+    for run in 1..=runs {
+        let groups = generator.generate(&mut random);
+        let inbox = flatten(&groups);
+        if let Some(reason) = fails(&inbox) {
+            let failure = shrink(generator, groups, reason, &mut fails);
+            return FuzzReport{seed, runs: run, failure: Some(Failure{inbox, ..failure})};
+        }
+    }
+
+    FuzzReport{seed, runs, failure: None}
+}
+
+// the inboxes fuzz tries with this seed, in order
+pub fn inboxes(generator: &Generator, runs: usize, seed: u64) -> Vec<Vec<Value>> {
+    let mut random = Random::new(seed);
+    (0..runs).map(|_| flatten(&generator.generate(&mut random))).collect()
+}
+
+// greedily takes the first smaller inbox that still fails, until none does
+fn shrink<F>(generator: &Generator, mut groups: Vec<Vec<Value>>, mut reason: String, fails: &mut F) -> Failure
+        where F: FnMut(&[Value]) -> Option<String> {
+    let mut shrinks = 0;
+    let mut attempts = 0;
+    'shrinking: while attempts < MAX_SHRINK_ATTEMPTS {
+        for candidate in generator.shrink(&groups) {
+            attempts += 1;
+            if let Some(candidate_reason) = fails(&flatten(&candidate)) {
+                groups = candidate;
+                reason = candidate_reason;
+                shrinks += 1;
+                continue 'shrinking;
+            }
+            if attempts >= MAX_SHRINK_ATTEMPTS {
+                break;
+            }
+        }
+        break;
+    }
+
+    Failure{inbox: vec!(), shrunk: flatten(&groups), reason, shrinks}
+}
+
+pub fn with_inbox(floor: &InternalState, inbox: &[Value]) -> InternalState {
+    let mut state = floor.clone();
+    state.input_tape = inbox.iter().rev().cloned().collect();
+    state.expected_output = None;
+    state
+}
+
+// the program must not stop with an error or go past the limits
+pub fn completes<'a>(operations: &'a [Operation], floor: &'a InternalState, limits: Limits) -> impl FnMut(&[Value]) -> Option<String> + 'a {
+    move |inbox| {
+        let mut state = with_inbox(floor, inbox);
+        let outcome = CodeIterator::without_dump(&mut state, operations.to_vec())
+            .with_limits(limits)
+            .run();
+        match outcome {
+            RunOutcome::RuntimeError(error) | RunOutcome::LimitExceeded(error) => Some(error.to_string()),
+            _ => None
+        }
+    }
+}
+
+// the program must behave like the reference one, as diff checks it
+pub fn agrees_with<'a>(reference: &'a [Operation], operations: &'a [Operation], floor: &'a InternalState, limits: Limits) -> impl FnMut(&[Value]) -> Option<String> + 'a {
+    move |inbox| {
+        compare("", reference, operations, &with_inbox(floor, inbox), limits)
+            .divergence
+            .map(|divergence| divergence.to_string())
+    }
+}
+
+impl fmt::Display for FuzzReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.failure {
+            None => writeln!(f, "no failure in {} inboxes (seed {})", self.runs, self.seed),
+            Some(ref failure) => {
+                writeln!(f, "inbox {} failed (seed {}): {}", self.runs, self.seed, describe_values(&failure.inbox))?;
+                writeln!(f, "shrunk in {} steps to {}", failure.shrinks, describe_values(&failure.shrunk))?;
+                writeln!(f, "{}", failure.reason)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use Value;
+    use Location;
+    use Operation;
+    use limits::Limits;
+    use state::InternalState;
+    use fuzz::{Item, Generator, Random, fuzz, completes, agrees_with};
+
+    fn number(value: i32) -> Value {
+        Value::Number{value}
+    }
+
+    #[test]
+    fn generators_respect_their_spec() {
+        let mut random = Random::new(7);
+        let strings = Generator::Strings{letters: vec!('a', 'b'), length: (1, 3), count: (2, 2)};
+        let pairs = Generator::Pairs{first: Item::Number{min: -3, max: 3}, second: Item::Letter{letters: vec!('x')}, count: (0, 4)};
+
+        for _ in 0..100 {
+            let generated = strings.generate(&mut random);
+            assert_eq!(generated.len(), 2);
+            for string in generated {
+                assert!(string.len() >= 2 && string.len() <= 4);
+                assert_eq!(string.last(), Some(&number(0)));
+                assert!(string[..string.len() - 1].iter().all(|value| *value == Value::Character{value: 'a'} || *value == Value::Character{value: 'b'}));
+            }
+            for pair in pairs.generate(&mut random) {
+                assert!(match pair[0] {
+                    Value::Number{value} => (-3..=3).contains(&value),
+                    _ => false
+                });
+                assert_eq!(pair[1], Value::Character{value: 'x'});
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_same_inboxes() {
+        let generator = Generator::Items{item: Item::Number{min: -999, max: 999}, count: (0, 10)};
+
+        assert_eq!(generator.generate(&mut Random::new(3)), generator.generate(&mut Random::new(3)));
+        assert!(Generator::Items{item: Item::Number{min: 1, max: 0}, count: (0, 1)}.validate().is_err());
+        assert!(Generator::Strings{letters: vec!(), length: (0, 1), count: (0, 1)}.validate().is_err());
+    }
+
+    // adds each pair: fails with an overflow on big numbers
+    fn add_pairs() -> Vec<Operation> {
+        vec!(
+            Operation::Label,
+            Operation::Inbox,
+            Operation::CopyTo{cell: Location::Cell(0)},
+            Operation::Inbox,
+            Operation::Add{cell: Location::Cell(0)},
+            Operation::Outbox,
+            Operation::Jump{next_operation: 0}
+        )
+    }
+
+    #[test]
+    fn shrinks_to_a_minimal_inbox() {
+        let generator = Generator::Pairs{first: Item::Number{min: -999, max: 999}, second: Item::Number{min: -999, max: 999}, count: (1, 8)};
+        let floor = InternalState::new(None, 0).with_memory(vec!(None));
+        let program = add_pairs();
+        let report = fuzz(&generator, 1000, 1, completes(&program, &floor, Limits::default()));
+
+        let failure = report.failure.unwrap();
+        // one pair, just past the range once shrunk
+        assert_eq!(failure.shrunk.len(), 2);
+        let sum = match (failure.shrunk[0], failure.shrunk[1]) {
+            (Value::Number{value: first}, Value::Number{value: second}) => first + second,
+            _ => 0
+        };
+        assert_eq!(sum.abs(), 1000);
+        assert!(failure.reason.contains("instruction 4"));
+    }
+
+    #[test]
+    fn shrinks_strings() {
+        // outputs the strings without their zero, except a bug on the letter c
+        let reference = vec!(
+            Operation::Label,
+            Operation::Inbox,
+            Operation::JumpEqualsZero{next_operation: 0},
+            Operation::Outbox,
+            Operation::Jump{next_operation: 0}
+        );
+        let mut buggy = reference.clone();
+        buggy.insert(3, Operation::Sub{cell: Location::Cell(0)});
+        buggy.insert(4, Operation::JumpEqualsZero{next_operation: 0});
+        buggy.insert(5, Operation::Add{cell: Location::Cell(0)});
+        let floor = InternalState::new(None, 0).with_memory(vec!(Some(Value::Character{value: 'c'})));
+        let generator = Generator::Strings{letters: vec!('a', 'b', 'c', 'd'), length: (0, 6), count: (1, 5)};
+        let report = fuzz(&generator, 1000, 5, agrees_with(&reference, &buggy, &floor, Limits::default()));
+
+        assert_eq!(report.failure.map(|failure| failure.shrunk), Some(vec!(Value::Character{value: 'c'}, number(0))));
+    }
+
+    #[test]
+    fn no_failure() {
+        let generator = Generator::Items{item: Item::Letter{letters: vec!('a', 'b')}, count: (0, 5)};
+        let program = vec!(Operation::Label, Operation::Inbox, Operation::Outbox, Operation::Jump{next_operation: 0});
+        let floor = InternalState::new(None, 0);
+        let report = fuzz(&generator, 200, 9, completes(&program, &floor, Limits::default()));
+
+        assert_eq!(report.failure, None);
+        assert_eq!(report.runs, 200);
+        assert_eq!(report.to_string(), "no failure in 200 inboxes (seed 9)\n");
+    }
+}
@@ -0,0 +1,294 @@
+use Value;
+use Operation;
+use memory::extract_memory_position;
+use state::InternalState;
+
+// compact record of what every executed instruction changed, so that the
+// execution can be walked backwards and forwards without running it again.
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct CellChange {
+    pub cell: usize,
+    pub old_value: Option<Value>,
+    pub new_value: Option<Value>
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Delta {
+    // executed instructions before this one ran
+    pub step: u32,
+    pub instruction: usize,
+    pub next_instruction: usize,
+    pub old_register: Option<Value>,
+    pub new_register: Option<Value>,
+    pub cell: Option<CellChange>,
+    pub taken_input: Option<Value>,
+    pub given_output: Option<Value>
+}
+
+// the floor tile an operation is going to write, if any
+fn written_cell(operation: Operation, s: &InternalState) -> Option<usize> {
+    match operation {
+        Operation::CopyTo{cell} | Operation::BumpPlus{cell} | Operation::BumpMinus{cell} =>
+            extract_memory_position(cell, s).ok(),
+        _ => None
+    }
+}
+
+impl Delta {
+    // takes note of the state before running the operation
+    pub fn before(operation: Operation, s: &InternalState) -> Self {
+        Delta {
+            step: s.executed_instructions(),
+            instruction: s.instruction_counter,
+            next_instruction: s.instruction_counter,
+            old_register: s.register,
+            new_register: s.register,
+            cell: written_cell(operation, s).map(|cell| {
+                CellChange{cell, old_value: s.memory[cell], new_value: s.memory[cell]}
+            }),
+            taken_input: s.input_tape.last().cloned(),
+            given_output: None
+        }
+    }
+
+    // completes the delta once the operation ran
+    pub fn after(mut self, operation: Operation, s: &InternalState) -> Self {
+        self.next_instruction = s.instruction_counter;
+        self.new_register = s.register;
+        if let Some(ref mut change) = self.cell {
+            change.new_value = s.memory[change.cell];
+        }
+        if operation != Operation::Inbox {
+            self.taken_input = None;
+        }
+        if operation == Operation::Outbox {
+            self.given_output = s.output_tape.last().cloned();
+        }
+        self
+    }
+
+    pub fn changes_cell(&self, cell: usize) -> bool {
+        match self.cell {
+            Some(change) => change.cell == cell && change.old_value != change.new_value,
+            None => false
+        }
+    }
+
+    pub fn undo(&self, s: &mut InternalState) {
+        s.instruction_counter = self.instruction;
+        s.register = self.old_register;
+        if let Some(change) = self.cell {
+            s.memory[change.cell] = change.old_value;
+        }
+        if let Some(value) = self.taken_input {
+            s.input_tape.push(value);
+        }
+        if self.given_output.is_some() {
+            s.output_tape.pop();
+        }
+        s.decrease_executed_instructions();
+    }
+
+    pub fn redo(&self, s: &mut InternalState) {
+        s.instruction_counter = self.next_instruction;
+        s.register = self.new_register;
+        if let Some(change) = self.cell {
+            s.memory[change.cell] = change.new_value;
+        }
+        if self.taken_input.is_some() {
+            s.input_tape.pop();
+        }
+        if let Some(value) = self.given_output {
+            s.output_tape.push(value);
+        }
+        s.increase_executed_instructions();
+    }
+}
+
+// deltas of the executed instructions: the ones before `position` are
+// applied to the state, the ones after it were undone and can be redone.
+// Changes made to the state by hand are not recorded.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct History {
+    deltas: Vec<Delta>,
+    position: usize
+}
+
+impl History {
+    pub fn new() -> Self {
+        History{deltas: vec!(), position: 0}
+    }
+
+    pub fn deltas(&self) -> &[Delta] {
+        &self.deltas
+    }
+
+    // deltas applied to the state
+    pub fn applied(&self) -> usize {
+        self.position
+    }
+
+    // running an instruction forgets the undone ones
+    pub fn record(&mut self, delta: Delta) {
+        self.deltas.truncate(self.position);
+        self.deltas.push(delta);
+        self.position += 1;
+    }
+
+    pub fn first_step(&self) -> Option<u32> {
+        self.deltas.first().map(|delta| delta.step)
+    }
+
+    pub fn last_step(&self) -> Option<u32> {
+        self.deltas.last().map(|delta| delta.step + 1)
+    }
+
+    pub fn step_back(&mut self, s: &mut InternalState) -> Option<Delta> {
+        if self.position == 0 {
+            return None;
+        }
+        self.position -= 1;
+        let delta = self.deltas[self.position];
+        delta.undo(s);
+        Some(delta)
+    }
+
+    pub fn step_forward(&mut self, s: &mut InternalState) -> Option<Delta> {
+        let delta = *self.deltas.get(self.position)?;
+        delta.redo(s);
+        self.position += 1;
+        Some(delta)
+    }
+
+    // moves the state to the given step; false if the history does not reach it
+    pub fn travel_to(&mut self, step: u32, s: &mut InternalState) -> bool {
+        match (self.first_step(), self.last_step()) {
+            (Some(first), Some(last)) if first <= step && step <= last => (),
+            _ => return false
+        }
+
+        // the deltas run out when the counter was changed by hand
+        while s.executed_instructions() > step {
+            if self.step_back(s).is_none() {
+                return false;
+            }
+        }
+        while s.executed_instructions() < step {
+            if self.step_forward(s).is_none() {
+                return false;
+            }
+        }
+        true
+    }
+
+    // the last applied delta changing the given floor tile
+    pub fn last_change_of_cell(&self, cell: usize) -> Option<&Delta> {
+        self.deltas[..self.position].iter().rev().find(|delta| delta.changes_cell(cell))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use Value;
+    use Location;
+    use Operation;
+    use history::{Delta, History};
+    use state::InternalState;
+
+    fn run(operations: &[Operation], s: &mut InternalState, history: &mut History) {
+        for &operation in operations {
+            let delta = Delta::before(operation, s);
+            s.apply(operation).unwrap();
+            history.record(delta.after(operation, s));
+        }
+    }
+
+    fn program() -> Vec<Operation> {
+        vec!(
+            Operation::Inbox,
+            Operation::CopyTo{cell: Location::Cell(1)},
+            Operation::BumpPlus{cell: Location::Cell(1)},
+            Operation::Outbox,
+            Operation::Inbox,
+            Operation::CopyTo{cell: Location::Address(0)}
+        )
+    }
+
+    fn state() -> InternalState {
+        InternalState::new(None, 0)
+            .with_input_tape(vec!(Value::Number{value: 7}, Value::Number{value: 4}))
+            .with_memory(vec!(Some(Value::Number{value: 1}), None))
+    }
+
+    #[test]
+    fn step_back_restores_every_state() {
+        let mut s = state();
+        let mut history = History::new();
+        let mut states = vec!(s.clone());
+        for &operation in program().iter() {
+            run(&[operation], &mut s, &mut history);
+            states.push(s.clone());
+        }
+
+        while history.step_back(&mut s).is_some() {
+            states.pop();
+            assert_eq!(&s, states.last().unwrap());
+        }
+        assert_eq!(s, state());
+    }
+
+    #[test]
+    fn travel_back_and_forth() {
+        let mut s = state();
+        let mut history = History::new();
+        run(&program(), &mut s, &mut history);
+        let end = s.clone();
+
+        assert!(history.travel_to(2, &mut s));
+        assert_eq!(s.executed_instructions(), 2);
+        assert_eq!(s.memory, vec!(Some(Value::Number{value: 1}), Some(Value::Number{value: 4})));
+        assert_eq!(s.output_tape, vec!());
+
+        assert!(history.travel_to(6, &mut s));
+        assert_eq!(s, end);
+        assert!(!history.travel_to(7, &mut s));
+    }
+
+    #[test]
+    fn travel_stops_when_the_deltas_run_out() {
+        let mut s = state();
+        let mut history = History::new();
+        run(&program(), &mut s, &mut history);
+        s.decrease_executed_instructions();
+
+        assert!(!history.travel_to(6, &mut s));
+        assert_eq!(history.applied(), 6);
+    }
+
+    #[test]
+    fn recording_forgets_undone_steps() {
+        let mut s = state();
+        let mut history = History::new();
+        run(&program(), &mut s, &mut history);
+
+        history.travel_to(3, &mut s);
+        run(&[Operation::Outbox], &mut s, &mut history);
+
+        assert_eq!(history.deltas().len(), 4);
+        assert_eq!(history.last_step(), Some(4));
+    }
+
+    #[test]
+    fn last_change_of_cell() {
+        let mut s = state();
+        let mut history = History::new();
+        run(&program(), &mut s, &mut history);
+
+        assert_eq!(history.last_change_of_cell(1).map(|delta| delta.step), Some(5));
+        assert_eq!(history.last_change_of_cell(0), None);
+
+        history.travel_to(4, &mut s);
+        assert_eq!(history.last_change_of_cell(1).map(|delta| delta.step), Some(2));
+    }
+}
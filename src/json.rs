@@ -3,10 +3,16 @@ use std::io::prelude::*;
 use serde_json;
 
 use Operation;
+use Program;
 use Value;
 use Location;
 use state::InternalState;
-use std::fs::OpenOptions;
+use level::{Level, Targets};
+use source::to_mnemonic;
+use suite::ManifestEntry;
+use fuzz::{Generator, Item};
+use error::{RuntimeError, LoadError, LoadErrorKind};
+use trace::{JsonLines, TraceSink};
 
 // JSON data format for json-ified source code
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -36,213 +42,415 @@ enum JsonValue {
 #[derive(Serialize, Deserialize, Clone)]
 struct Config {
     input_tape: Vec<JsonValue>,
-    memory: Vec<Option<JsonValue>>
+    memory: Vec<Option<JsonValue>>,
+    // defaults to the length of memory
+    #[serde(default)]
+    floor_size: Option<usize>,
+    // in the order the items should come out
+    #[serde(default)]
+    expected_output: Option<Vec<JsonValue>>
+}
+
+// JSON data format for level files
+#[derive(Serialize, Deserialize, Clone)]
+struct JsonLevel {
+    #[serde(default)]
+    name: Option<String>,
+    inbox: Vec<JsonValue>,
+    #[serde(default)]
+    memory: Vec<Option<JsonValue>>,
+    #[serde(default)]
+    floor_size: Option<usize>,
+    #[serde(default)]
+    allowed_instructions: Option<Vec<String>>,
+    expected_outbox: Vec<JsonValue>,
+    #[serde(default)]
+    size_target: Option<usize>,
+    #[serde(default)]
+    speed_target: Option<u32>,
+    #[serde(default)]
+    generator: Option<JsonGenerator>
+}
+
+// JSON data format for fuzzing generators, like
+// {"kind": "pairs", "first": {"min": -9, "max": 9}, "second": {"letters": "abc"}, "count": [1, 5]}
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+enum JsonItem {
+    Number{min: i32, max: i32},
+    Letter{letters: String}
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum JsonGenerator {
+    Items{item: JsonItem, count: (usize, usize)},
+    Strings{letters: String, length: (usize, usize), count: (usize, usize)},
+    Pairs{first: JsonItem, second: JsonItem, count: (usize, usize)}
+}
+
+// JSON data format for test manifests
+#[derive(Serialize, Deserialize, Clone)]
+struct JsonManifestEntry {
+    #[serde(default)]
+    name: Option<String>,
+    input: String
 }
 
 impl JsonOperation {
-    fn new(operation: String, operand: Option<JsonOperand>) -> Self {
+    pub fn new(operation: String, operand: Option<JsonOperand>) -> Self {
         Self {
-            operation: operation,
-            operand: operand
+            operation,
+            operand
         }
     }
-}
 
-fn to_operator(json_op: JsonOperation, labels_mapping: &Vec<(String, usize)>) -> Operation {
-    if json_op.operation == String::from("inbox"){ return Operation::Inbox{}; }
-    else if json_op.operation == String::from("add") {
-        let cell_to_add = match json_op.operand.unwrap() {
-            JsonOperand::Label(_) => panic!("only Address or Cell are valid operand for 'add'"),
-            JsonOperand::Address(cell) => Location::Address(cell as usize),
-            JsonOperand::Cell(cell) => Location::Cell(cell as usize)
-        };
-        return Operation::Add{cell: cell_to_add};
+    fn describe_operand(&self) -> String {
+        match self.operand {
+            Some(JsonOperand::Label(ref label)) => format!("label {}", label),
+            Some(JsonOperand::Address(cell)) => format!("address [{}]", cell),
+            Some(JsonOperand::Cell(cell)) => format!("cell {}", cell),
+            None => String::from("nothing")
+        }
     }
-    else if json_op.operation == String::from("sub") {
-        let cell_to_sub = match json_op.operand.unwrap() {
-            JsonOperand::Label(_) => panic!("only Address or Cell are valid operand for 'sub'"),
-            JsonOperand::Address(cell) => Location::Address(cell as usize),
-            JsonOperand::Cell(cell) => Location::Cell(cell as usize)
-        };
-        return Operation::Sub{cell: cell_to_sub};
+
+    fn invalid_operand(&self) -> LoadErrorKind {
+        LoadErrorKind::InvalidOperand{operation: self.operation.clone(), operand: self.describe_operand()}
     }
-    else if json_op.operation == String::from("copyfrom") {
-        let cell = match json_op.operand.unwrap() {
-            JsonOperand::Label(_) => panic!("only Address or Cell are valid operand for 'copyfrom'"),
-            JsonOperand::Address(cell) => Location::Address(cell as usize),
-            JsonOperand::Cell(cell) => Location::Cell(cell as usize)
-        };
-        return Operation::CopyFrom{cell: cell};
+}
+
+fn to_location(json_op: &JsonOperation) -> Result<Location, LoadErrorKind> {
+    match json_op.operand {
+        Some(JsonOperand::Address(cell)) => Ok(Location::Address(cell as usize)),
+        Some(JsonOperand::Cell(cell)) => Ok(Location::Cell(cell as usize)),
+        Some(JsonOperand::Label(_)) => Err(json_op.invalid_operand()),
+        None => Err(LoadErrorKind::MissingOperand{operation: json_op.operation.clone()})
     }
-    else if json_op.operation == String::from("copyto") {
-        let cell = match json_op.operand.unwrap() {
-            JsonOperand::Label(_) => panic!("only Address or Cell are valid operand for 'copyto'"),
-            JsonOperand::Address(cell) => Location::Address(cell as usize),
-            JsonOperand::Cell(cell) => Location::Cell(cell as usize)
-        };
-        return Operation::CopyTo{cell: cell};
+}
+
+fn to_jump_target(json_op: &JsonOperation, labels_mapping: &[(String, usize)]) -> Result<usize, LoadErrorKind> {
+    match json_op.operand {
+        Some(JsonOperand::Label(ref label_name)) =>
+            position_from_label(label_name, labels_mapping)
+                .ok_or_else(|| LoadErrorKind::UnknownLabel{label: label_name.clone()}),
+        Some(_) => Err(json_op.invalid_operand()),
+        None => Err(LoadErrorKind::MissingOperand{operation: json_op.operation.clone()})
     }
-    else if json_op.operation == String::from("bump+") {
-        let cell = match json_op.operand.unwrap() {
-            JsonOperand::Label(_) => panic!("only Address or Cell are valid operand for 'bump+'"),
-            JsonOperand::Address(cell) => Location::Address(cell as usize),
-            JsonOperand::Cell(cell) => Location::Cell(cell as usize)
-        };
-        return Operation::BumpPlus{cell: cell};
+}
+
+fn to_operator(json_op: JsonOperation, labels_mapping: &[(String, usize)]) -> Result<Operation, LoadErrorKind> {
+    match json_op.operation.as_str() {
+        "inbox" => Ok(Operation::Inbox{}),
+        "outbox" => Ok(Operation::Outbox{}),
+        "add" => Ok(Operation::Add{cell: to_location(&json_op)?}),
+        "sub" => Ok(Operation::Sub{cell: to_location(&json_op)?}),
+        "copyfrom" => Ok(Operation::CopyFrom{cell: to_location(&json_op)?}),
+        "copyto" => Ok(Operation::CopyTo{cell: to_location(&json_op)?}),
+        "bump+" => Ok(Operation::BumpPlus{cell: to_location(&json_op)?}),
+        "bump-" => Ok(Operation::BumpMinus{cell: to_location(&json_op)?}),
+        "label" => Ok(Operation::Label{}),
+        "jmp" => Ok(Operation::Jump{next_operation: to_jump_target(&json_op, labels_mapping)?}),
+        "jneg" => Ok(Operation::JumpNegative{next_operation: to_jump_target(&json_op, labels_mapping)?}),
+        "jez" => Ok(Operation::JumpEqualsZero{next_operation: to_jump_target(&json_op, labels_mapping)?}),
+        _ => Err(LoadErrorKind::UnknownOperation{operation: json_op.operation.clone()})
     }
-    else if json_op.operation == String::from("bump-") {
-        let cell = match json_op.operand.unwrap() {
-            JsonOperand::Label(_) => panic!("only Address or Cell are valid operand for 'bump-'"),
-            JsonOperand::Address(cell) => Location::Address(cell as usize),
-            JsonOperand::Cell(cell) => Location::Cell(cell as usize)
+}
+
+fn labels_to_positions(source_code: &[Option<JsonOperation>]) -> Vec<(String, usize)> {
+    let mut labels : Vec<(String, usize)> = vec!();
+
+    for (index, operation) in source_code.iter().enumerate() {
+        let operation = match *operation {
+            Some(ref operation) => operation,
+            None => continue
         };
-        return Operation::BumpMinus{cell: cell};
-    }
-    else if json_op.operation == String::from("label") {
-        return Operation::Label{};
-    }
-    else if json_op.operation == String::from("jmp") {
-        if let JsonOperand::Label(label_name) = json_op.operand.unwrap() {
-            let next_position = position_from_label(&label_name, &labels_mapping).unwrap();
-            return Operation::Jump{next_operation: next_position};
-        }
-        else {
-            panic!("only Labels are valid operands for jmp");
+        if operation.operation == "label" {
+            if let Some(JsonOperand::Label(ref label_name)) = operation.operand {
+                labels.push((label_name.clone(), index));
+            }
         }
     }
-    else if json_op.operation == String::from("jneg") {
-        if let JsonOperand::Label(label_name) = json_op.operand.unwrap() {
-            let next_position = position_from_label(&label_name, &labels_mapping).unwrap();
-            return Operation::JumpNegative{next_operation: next_position};
+
+    labels
+}
+
+fn position_from_label(label: &str, mapping: &[(String, usize)]) -> Option<usize> {
+    mapping.iter()
+        .rev()
+        .find(|pair| pair.0 == label)
+        .map(|&(_, position)| position)
+}
+
+// line and column (both starting from 1) where every element
+// of the top-level array starts, used to point errors to the json source.
+fn element_positions(serialized_code: &str) -> Vec<(usize, usize)> {
+    let mut positions = vec!();
+    let (mut line, mut column) = (1, 0);
+    let mut depth = 0;
+    let (mut in_string, mut escaped) = (false, false);
+    let mut expecting_element = false;
+
+    for c in serialized_code.chars() {
+        if c == '\n' {
+            line += 1;
+            column = 0;
+            continue;
         }
-        else {
-            panic!("only Labels are valid operands for jneg");
+        column += 1;
+
+        if in_string {
+            if escaped { escaped = false; }
+            else if c == '\\' { escaped = true; }
+            else if c == '"' { in_string = false; }
+            continue;
         }
-    }
-    else if json_op.operation == String::from("jez") {
-        if let JsonOperand::Label(label_name) = json_op.operand.unwrap() {
-            let next_position = position_from_label(&label_name, &labels_mapping).unwrap();
-            return Operation::JumpEqualsZero{next_operation: next_position};
+        if c.is_whitespace() {
+            continue;
         }
-        else {
-            panic!("only Labels are valid operands for jez");
+        if depth == 1 && expecting_element && c != ']' {
+            positions.push((line, column));
+            expecting_element = false;
+        }
+        match c {
+            '"' => in_string = true,
+            '[' | '{' => {
+                depth += 1;
+                expecting_element = depth == 1;
+            },
+            ']' | '}' => depth -= 1,
+            ',' if depth == 1 => expecting_element = true,
+            _ => ()
         }
     }
-    else if json_op.operation == String::from("outbox") { return Operation::Outbox{}; }
-    else { panic!(format!("unrecognized operation {}", json_op.operation)) }
+
+    positions
 }
 
-fn labels_to_positions(source_code: &Vec<JsonOperation>) -> Vec<(String, usize)> {
-    let mut labels : Vec<(String, usize)> = vec!();
+fn syntax_error(error: &serde_json::Error) -> LoadError {
+    LoadError::new(LoadErrorKind::Syntax{reason: error.to_string()})
+        .at_position(error.line(), error.column())
+}
 
-    for (index, operation) in source_code.iter().enumerate() {
-        if operation.operation == String::from("label") {
-            if let JsonOperand::Label(label_name) = operation.clone().operand.unwrap() {
-                labels.push((label_name, index));
+pub fn read_contents(path: &str) -> Result<String, LoadError> {
+    let mut contents = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut contents))
+        .map_err(|reason| LoadError::new(LoadErrorKind::Io{reason: reason.to_string()}))?;
+
+    Ok(contents)
+}
+
+pub fn read_file(srcpath: String) -> Result<Vec<Operation>, Vec<LoadError>> {
+    read_program_file(srcpath).map(|program| program.operations)
+}
+
+pub fn read_instructions(serialized_code: String) -> Result<Vec<Operation>, Vec<LoadError>> {
+    read_program(serialized_code).map(|program| program.operations)
+}
+
+pub fn read_program_file(srcpath: String) -> Result<Program, Vec<LoadError>> {
+    let contents = read_contents(&srcpath).map_err(|error| vec!(error))?;
+    read_program(contents)
+}
+
+// every malformed instruction is reported, not just the first one.
+pub fn read_program(serialized_code: String) -> Result<Program, Vec<LoadError>> {
+    let elements: Vec<serde_json::Value> = serde_json::from_str(&serialized_code)
+        .map_err(|error| vec!(syntax_error(&error)))?;
+    let positions = element_positions(&serialized_code);
+
+    let mut source_code: Vec<Option<JsonOperation>> = vec!();
+    let mut errors: Vec<LoadError> = vec!();
+    for (index, element) in elements.into_iter().enumerate() {
+        match serde_json::from_value(element) {
+            Ok(json_op) => source_code.push(Some(json_op)),
+            Err(reason) => {
+                let mut error = LoadError::new(LoadErrorKind::Syntax{reason: reason.to_string()})
+                    .at_instruction(index);
+                if let Some(&(line, column)) = positions.get(index) {
+                    error = error.at_position(line, column);
+                }
+                errors.push(error);
+                source_code.push(None);
             }
         }
     }
 
-   return labels;
+    to_program(source_code, &positions)
+        .map_err(|more_errors| merge_load_errors(errors, more_errors))
 }
 
-fn position_from_label(label: &String, mapping: &Vec<(String, usize)>) -> Option<usize> {
-    mapping.iter().filter_map(|pair| {
-        let &(ref candidate_label, position) = pair;
-        if label == candidate_label {
-            return Some(position);
-        }
-        else {
-            return None
+// joins the errors found while parsing some source code with the ones
+// found by to_program, in the order of the instructions.
+pub fn merge_load_errors(mut errors: Vec<LoadError>, more_errors: Vec<LoadError>) -> Vec<LoadError> {
+    errors.extend(more_errors);
+    errors.sort_by_key(|error| error.instruction);
+    errors
+}
+
+// resolves labels and converts json-ified source code to operations.
+// shared with the other source formats, which are parsed to JsonOperations first.
+// None stands for an instruction that could not be parsed: it keeps the index of
+// the following ones, and the program is not built, the parser reporting why.
+// positions holds the (line, column) of every operation, when known.
+pub fn to_program(source_code: Vec<Option<JsonOperation>>, positions: &[(usize, usize)]) -> Result<Program, Vec<LoadError>> {
+    let position_for_label = labels_to_positions(&source_code);
+    let complete = source_code.iter().all(|json_op| json_op.is_some());
+    let mut res: Vec<Operation> = vec!();
+    let mut errors: Vec<LoadError> = vec!();
+    for (index, json_op) in source_code.into_iter().enumerate() {
+        let json_op = match json_op {
+            Some(json_op) => json_op,
+            None => continue
+        };
+        match to_operator(json_op, &position_for_label) {
+            Ok(operation) => res.push(operation),
+            Err(kind) => {
+                let mut error = LoadError::new(kind).at_instruction(index);
+                if let Some(&(line, column)) = positions.get(index) {
+                    error = error.at_position(line, column);
+                }
+                errors.push(error);
+            }
         }
-    }).last()
+    }
+
+    if errors.is_empty() && complete {
+        Ok(Program{operations: res, labels: position_for_label})
+    }
+    else {
+        Err(errors)
+    }
 }
 
-pub fn read_file(srcpath: String) -> Vec<Operation> {
-    let mut file = File::open(srcpath).unwrap();
-    let mut contents = String::new();
-    let file_read_ok = file.read_to_string(&mut contents);
-    if file_read_ok.is_err() {
-        panic!("could not read the file!");
+fn to_value(json_value: JsonValue) -> Value {
+    match json_value {
+        JsonValue::Number(num_) => Value::Number { value: num_ },
+        JsonValue::Character(char_) => Value::Character { value: char_ }
     }
+}
 
-    read_instructions(contents)
+// floor_size defaults to the tiles given in memory, and cannot leave some of them out
+fn checked_floor_size(floor_size: Option<usize>, tiles: usize) -> Result<usize, LoadError> {
+    match floor_size {
+        Some(floor_size) if floor_size < tiles => Err(LoadError::new(LoadErrorKind::FloorTooSmall{floor_size, tiles})),
+        Some(floor_size) => Ok(floor_size),
+        None => Ok(tiles)
+    }
 }
 
-pub fn read_instructions(serialized_code: String) -> Vec<Operation> {
-    let source_code: Vec<JsonOperation> = serde_json::from_str(&serialized_code).unwrap();
-    let position_for_label = labels_to_positions(&source_code);
-    let mut res: Vec<Operation> = vec!();
-    for json_op in source_code {
-        res.push(to_operator(json_op.clone(), &position_for_label));
+pub fn read_config(path: String) -> Result<InternalState, LoadError> {
+    let contents = read_contents(&path)?;
+    read_config_from_string(contents)
+}
+
+pub fn read_config_from_string(serialized_input: String) -> Result<InternalState, LoadError> {
+    let input_config: Config = serde_json::from_str(&serialized_input)
+        .map_err(|error| syntax_error(&error))?;
+    let floor_size = checked_floor_size(input_config.floor_size, input_config.memory.len())?;
+    Ok(InternalState::new(None, 0)
+        .with_input_tape(input_config.input_tape.into_iter().map(to_value).collect())
+        .with_memory(input_config.memory.into_iter().map(|memory_value| memory_value.map(to_value)).collect())
+        .with_empty_tiles_up_to(floor_size)
+        .with_expected_output(input_config.expected_output.map(|output| output.into_iter().map(to_value).collect())))
+}
+
+pub fn read_level(path: &str) -> Result<Level, LoadError> {
+    let contents = read_contents(path)?;
+    read_level_from_string(&contents)
+}
+
+pub fn read_level_from_string(serialized_level: &str) -> Result<Level, LoadError> {
+    let level: JsonLevel = serde_json::from_str(serialized_level)
+        .map_err(|error| syntax_error(&error))?;
+    let unknown = level.allowed_instructions.iter()
+        .flat_map(|allowed| allowed.iter())
+        .find(|name| to_mnemonic(name).is_none());
+    if let Some(name) = unknown {
+        return Err(LoadError::new(LoadErrorKind::UnknownOperation{operation: name.clone()}));
     }
 
-    return res;
+    let floor_size = checked_floor_size(level.floor_size, level.memory.len())?;
+
+    Ok(Level {
+        name: level.name,
+        floor_size,
+        inbox: level.inbox.into_iter().map(to_value).collect(),
+        memory: level.memory.into_iter().map(|memory_value| memory_value.map(to_value)).collect(),
+        allowed_instructions: level.allowed_instructions,
+        expected_outbox: level.expected_outbox.into_iter().map(to_value).collect(),
+        targets: Targets{size: level.size_target, speed: level.speed_target},
+        generator: level.generator.map(to_generator).transpose()?
+    })
 }
 
-pub fn read_config(path: String) -> InternalState  {
-    let mut file = File::open(path).unwrap();
-    let mut contents = String::new();
-    let file_read_ok = file.read_to_string(&mut contents);
-    if file_read_ok.is_err() {
-        panic!("could not read the file!");
+fn to_item(json_item: JsonItem) -> Item {
+    match json_item {
+        JsonItem::Number{min, max} => Item::Number{min, max},
+        JsonItem::Letter{letters} => Item::Letter{letters: letters.chars().collect()}
     }
+}
 
-    read_config_from_string(contents)
+fn to_generator(json_generator: JsonGenerator) -> Result<Generator, LoadError> {
+    let generator = match json_generator {
+        JsonGenerator::Items{item, count} => Generator::Items{item: to_item(item), count},
+        JsonGenerator::Strings{letters, length, count} => Generator::Strings{letters: letters.chars().collect(), length, count},
+        JsonGenerator::Pairs{first, second, count} => Generator::Pairs{first: to_item(first), second: to_item(second), count}
+    };
+
+    generator.validate()
+        .map(|_| generator)
+        .map_err(|reason| LoadError::new(LoadErrorKind::Syntax{reason}))
+}
+
+pub fn read_generator(path: &str) -> Result<Generator, LoadError> {
+    let contents = read_contents(path)?;
+    read_generator_from_string(&contents)
 }
 
-pub fn read_config_from_string(serialized_input: String) -> InternalState {
-    let input_config: Config = serde_json::from_str(&serialized_input).unwrap();
-    return InternalState::new(None, 0)
-        .with_input_tape(input_config.input_tape.into_iter().map(|input| match input {
-            JsonValue::Number(num_) => Value::Number { value: num_ },
-            JsonValue::Character(char_) => Value::Character { value: char_ }
-        }).collect())
-        .with_memory(input_config.memory.into_iter().map(|memory_value| match memory_value {
-            Some(JsonValue::Number(num_)) => Some(Value::Number { value: num_ }),
-            Some(JsonValue::Character(char_)) => Some(Value::Character { value: char_ }),
-            None => None
-        }).collect())
-        .clone();
+pub fn read_generator_from_string(serialized_generator: &str) -> Result<Generator, LoadError> {
+    let generator: JsonGenerator = serde_json::from_str(serialized_generator)
+        .map_err(|error| syntax_error(&error))?;
+    to_generator(generator)
+}
+
+pub fn read_manifest(path: &str) -> Result<Vec<ManifestEntry>, LoadError> {
+    let contents = read_contents(path)?;
+    let entries: Vec<JsonManifestEntry> = serde_json::from_str(&contents)
+        .map_err(|error| syntax_error(&error))?;
+
+    Ok(entries.into_iter()
+        .map(|entry| ManifestEntry{name: entry.name, input: entry.input})
+        .collect())
 }
 
 #[derive(Serialize)]
 pub struct StateDump {
     internal_state: InternalState,
     ended_with_error: bool,
-    error_reason: String
+    error_reason: Option<RuntimeError>
 }
 
-pub fn serialize_state(internal_state: &InternalState, error_reason: &String) -> String {
-    let state_dump = StateDump {
-        internal_state: internal_state.clone(),
-        ended_with_error: !error_reason.is_empty(),
-        error_reason: error_reason.to_string(),
-    };
-
-    serde_json::to_string(&state_dump).unwrap()
+impl StateDump {
+    fn new(internal_state: &InternalState, error_reason: Option<&RuntimeError>) -> Self {
+        StateDump {
+            internal_state: internal_state.clone(),
+            ended_with_error: error_reason.is_some(),
+            error_reason: error_reason.cloned()
+        }
+    }
 }
 
-pub fn dump_state(internal_state: &InternalState, srcpath: &str, error_reason: &String) {
-    let state_dump = StateDump {
-        internal_state: internal_state.clone(),
-        ended_with_error: !error_reason.is_empty(),
-        error_reason: error_reason.to_string()
-    };
-
-    let raw_state = serde_json::to_string(&state_dump).unwrap();
+pub fn serialize_state(internal_state: &InternalState, error_reason: Option<&RuntimeError>) -> String {
+    serde_json::to_string(&StateDump::new(internal_state, error_reason)).unwrap()
+}
 
-    let result = OpenOptions::new()
-        .append(true).create(true).truncate(false)
-        .open(srcpath);
-    if result.is_ok() {
-        let mut file = result.unwrap();
-        file.write(raw_state.as_bytes());
-        // hrm-proxy expects a single line for each execution state --> add line separator
-        file.write(b"\n");
-    }
-    else {
-        panic!("cannot write the file?!?!?! {:?}", result.err());
+// appends a single state to the file; CodeIterator keeps its sink open instead
+pub fn dump_state(internal_state: &InternalState, srcpath: &str, error_reason: Option<&RuntimeError>) {
+    let written = JsonLines::append_to(srcpath)
+        .and_then(|mut sink| sink.record(internal_state, error_reason).and_then(|_| sink.flush()));
+    if let Err(reason) = written {
+        panic!("cannot write the file?!?!?! {:?}", reason);
     }
 }
 
@@ -253,9 +461,15 @@ mod test {
     use json::JsonOperation;
     use json::JsonOperand;
     use json::labels_to_positions;
+    use json::read_instructions;
+    use json::read_config_from_string;
+    use json::read_level_from_string;
+    use json::read_generator_from_string;
+    use fuzz::{Generator, Item};
+    use error::LoadErrorKind;
+    use Value;
 
     #[test]
-    #[should_panic]
     fn to_operator_unknown() {
         let empty_labels_mapping = vec!();
         let src = JsonOperation{
@@ -263,7 +477,9 @@ mod test {
             operand: None
         };
 
-        to_operator(src, &empty_labels_mapping);
+        let result = to_operator(src, &empty_labels_mapping);
+
+        assert_eq!(result, Err(LoadErrorKind::UnknownOperation{operation: String::from("fsdfsadfsadjsdf")}));
     }
 
     #[test]
@@ -276,7 +492,7 @@ mod test {
         let result = to_operator(src, &empty_labels_mapping);
 
         assert!(match result {
-            Operation::Label => true,
+            Ok(Operation::Label) => true,
             _ => false
         });
     }
@@ -292,13 +508,12 @@ mod test {
         let result = to_operator(operation, &mapping);
 
         assert!(match result {
-            Operation::Jump{next_operation: 3} => true,
+            Ok(Operation::Jump{next_operation: 3}) => true,
             _ => false
         });
     }
 
     #[test]
-    #[should_panic]
     fn to_operator_jump_label_not_found() {
         let mapping = vec!((String::from("myLabel"), 3));
         let operation = JsonOperation{
@@ -306,34 +521,152 @@ mod test {
             operand: Some(JsonOperand::Label(String::from("fdfsdfsadj")))
         };
 
-        to_operator(operation, &mapping);
+        let result = to_operator(operation, &mapping);
+
+        assert_eq!(result, Err(LoadErrorKind::UnknownLabel{label: String::from("fdfsdfsadj")}));
+    }
+
+    #[test]
+    fn to_operator_wrong_operand() {
+        let operation = JsonOperation::new(String::from("add"), Some(JsonOperand::Label(String::from("a"))));
+
+        let result = to_operator(operation, &[]);
+
+        assert_eq!(result, Err(LoadErrorKind::InvalidOperand{
+            operation: String::from("add"),
+            operand: String::from("label a")
+        }));
+    }
+
+    #[test]
+    fn read_instructions_reports_every_error() {
+        let code = String::from("[\n\
+            {\"operation\": \"inbox\", \"operand\": null},\n\
+            {\"operation\": \"teleport\", \"operand\": null},\n\
+            {\"operand\": null},\n  \
+              {\"operation\": \"jmp\", \"operand\": {\"Label\": \"nowhere\"}}\n\
+            ]");
+
+        let errors = read_instructions(code).unwrap_err();
+
+        assert_eq!(errors.len(), 3);
+        assert_eq!(errors[0].kind, LoadErrorKind::UnknownOperation{operation: String::from("teleport")});
+        assert_eq!((errors[0].instruction, errors[0].line, errors[0].column), (Some(1), Some(3), Some(1)));
+        assert!(match errors[1].kind {
+            LoadErrorKind::Syntax{..} => true,
+            _ => false
+        });
+        assert_eq!((errors[1].instruction, errors[1].line), (Some(2), Some(4)));
+        assert_eq!(errors[2].kind, LoadErrorKind::UnknownLabel{label: String::from("nowhere")});
+        assert_eq!((errors[2].line, errors[2].column), (Some(5), Some(3)));
+    }
+
+    #[test]
+    fn read_instructions_unparsed_and_bare_label() {
+        let code = String::from("[{\"operand\": null}, {\"operation\": \"label\", \"operand\": null}, {\"operation\": \"inbox\", \"operand\": null}]");
+
+        let errors = read_instructions(code).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].instruction, Some(0));
+        assert_eq!(read_instructions(String::from("[{\"operation\": \"label\", \"operand\": null}]")), Ok(vec!(Operation::Label)));
+    }
+
+    #[test]
+    fn read_instructions_invalid_json() {
+        let errors = read_instructions(String::from("[{\"operation\": ")).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, Some(1));
+    }
+
+    #[test]
+    fn read_config_floor_size() {
+        let result = read_config_from_string(String::from("{\"input_tape\": [], \"memory\": [1], \"floor_size\": 3}")).unwrap();
+
+        assert_eq!(result.floor_size(), 3);
+        assert_eq!(result.memory, vec!(Some(Value::Number{value: 1}), None, None));
+    }
+
+    #[test]
+    fn floor_size_smaller_than_memory() {
+        let config = read_config_from_string(String::from("{\"input_tape\": [], \"memory\": [1, 2, 3], \"floor_size\": 1}"));
+        let level = read_level_from_string("{\"inbox\": [], \"memory\": [1, 2], \"floor_size\": 0, \"expected_outbox\": []}");
+
+        assert_eq!(config.map_err(|error| error.kind), Err(LoadErrorKind::FloorTooSmall{floor_size: 1, tiles: 3}));
+        assert_eq!(level.map_err(|error| error.kind), Err(LoadErrorKind::FloorTooSmall{floor_size: 0, tiles: 2}));
+    }
+
+    #[test]
+    fn read_level() {
+        let result = read_level_from_string("{\"name\": \"Mail Room\", \"inbox\": [1, \"b\"], \"floor_size\": 2, \
+            \"allowed_instructions\": [\"INBOX\", \"outbox\"], \"expected_outbox\": [1, \"b\"]}").unwrap();
+
+        assert_eq!(result.name, Some(String::from("Mail Room")));
+        assert_eq!(result.inbox, vec!(Value::Number{value: 1}, Value::Character{value: 'b'}));
+        assert_eq!(result.initial_state().memory, vec!(None, None));
+        assert_eq!(result.expected_outbox, result.inbox);
+        assert_eq!(result.targets.size, None);
+    }
+
+    #[test]
+    fn read_level_unknown_instruction() {
+        let result = read_level_from_string("{\"inbox\": [], \"allowed_instructions\": [\"teleport\"], \"expected_outbox\": []}");
+
+        assert_eq!(result.map_err(|error| error.kind), Err(LoadErrorKind::UnknownOperation{operation: String::from("teleport")}));
+    }
+
+    #[test]
+    fn read_generators() {
+        let pairs = read_generator_from_string("{\"kind\": \"pairs\", \"first\": {\"min\": -9, \"max\": 9}, \
+            \"second\": {\"letters\": \"ab\"}, \"count\": [1, 5]}");
+        let level = read_level_from_string("{\"inbox\": [], \"expected_outbox\": [], \
+            \"generator\": {\"kind\": \"strings\", \"letters\": \"xyz\", \"length\": [0, 3], \"count\": [1, 2]}}").unwrap();
+
+        assert_eq!(pairs.unwrap(), Generator::Pairs{first: Item::Number{min: -9, max: 9}, second: Item::Letter{letters: vec!('a', 'b')}, count: (1, 5)});
+        assert_eq!(level.generator, Some(Generator::Strings{letters: vec!('x', 'y', 'z'), length: (0, 3), count: (1, 2)}));
+        assert!(read_generator_from_string("{\"kind\": \"items\", \"item\": {\"letters\": \"\"}, \"count\": [0, 1]}").is_err());
+    }
+
+    #[test]
+    fn read_config_expected_output() {
+        let result = read_config_from_string(String::from("{\"input_tape\": [], \"memory\": [], \"expected_output\": [1, \"a\"]}")).unwrap();
+
+        assert_eq!(result.expected_output, Some(vec!(Value::Number{value: 1}, Value::Character{value: 'a'})));
+    }
+
+    #[test]
+    fn read_config_invalid_json() {
+        let result = read_config_from_string(String::from("{\"input_tape\": [1, 2]}"));
+
+        assert!(result.is_err());
     }
 
     #[test]
     fn labels_to_positions_empty_code() {
         let empty_vec = vec!();
         let result = labels_to_positions(&empty_vec);
-        assert!(result.len() == 0);
+        assert!(result.is_empty());
     }
 
     #[test]
     fn labels_to_positions_no_labels() {
         let operations = vec!(
-            JsonOperation{
+            Some(JsonOperation{
                 operation: String::from("copyto"),
                 operand: Some(JsonOperand::Cell(2))
-        });
+        }));
         let result = labels_to_positions(&operations);
-        assert!(result.len() == 0);
+        assert!(result.is_empty());
     }
 
     #[test]
     fn labels_to_positions_with_labels() {
         let operations = vec!(
-            JsonOperation::new(String::from("label"), Some(JsonOperand::Label(String::from("firstlabel")))),
-            JsonOperation::new(String::from("inbox"), None),
-            JsonOperation::new(String::from("label"), Some(JsonOperand::Label(String::from("secondlabel")))),
-            JsonOperation::new(String::from("jmp"), Some(JsonOperand::Label(String::from("firstlabel"))))
+            Some(JsonOperation::new(String::from("label"), Some(JsonOperand::Label(String::from("firstlabel"))))),
+            Some(JsonOperation::new(String::from("inbox"), None)),
+            Some(JsonOperation::new(String::from("label"), Some(JsonOperand::Label(String::from("secondlabel"))))),
+            Some(JsonOperation::new(String::from("jmp"), Some(JsonOperand::Label(String::from("firstlabel")))))
         );
 
         let result = labels_to_positions(&operations);
@@ -0,0 +1,275 @@
+use std::fmt;
+
+use Value;
+use describe_value;
+use Operation;
+use CodeIterator;
+use RunOutcome;
+use fuzz::Generator;
+use limits::Limits;
+use state::{InternalState, Semantics};
+use error::{RuntimeError, ErrorKind};
+
+// a puzzle of the game: what the office looks like when the program starts,
+// and what the boss expects in the outbox.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Level {
+    pub name: Option<String>,
+    // in the order the items come in, unlike the input_tape of input files
+    pub inbox: Vec<Value>,
+    pub memory: Vec<Option<Value>>,
+    pub floor_size: usize,
+    // game names, like COPYFROM or JUMPZ; None allows every instruction
+    pub allowed_instructions: Option<Vec<String>>,
+    pub expected_outbox: Vec<Value>,
+    pub targets: Targets,
+    // how to make other inboxes for the level, to fuzz programs
+    pub generator: Option<Generator>
+}
+
+// the size and speed challenges of the level
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Targets {
+    // instructions, labels excluded
+    pub size: Option<usize>,
+    // average executed instructions
+    pub speed: Option<u32>
+}
+
+// the name of the instruction in the game; labels are not instructions there
+pub fn game_name(operation: Operation) -> Option<&'static str> {
+    match operation {
+        Operation::Inbox => Some("INBOX"),
+        Operation::Outbox => Some("OUTBOX"),
+        Operation::CopyFrom{..} => Some("COPYFROM"),
+        Operation::CopyTo{..} => Some("COPYTO"),
+        Operation::Add{..} => Some("ADD"),
+        Operation::Sub{..} => Some("SUB"),
+        Operation::BumpPlus{..} => Some("BUMPUP"),
+        Operation::BumpMinus{..} => Some("BUMPDN"),
+        Operation::Jump{..} => Some("JUMP"),
+        Operation::JumpEqualsZero{..} => Some("JUMPZ"),
+        Operation::JumpNegative{..} => Some("JUMPN"),
+        Operation::Label => None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    Pass,
+    ForbiddenInstruction{instruction: usize, operation: Operation},
+    // a None is a missing item, or one that should not be there
+    WrongOutbox{position: usize, expected: Option<Value>, actual: Option<Value>},
+    RuntimeError(RuntimeError),
+    LimitExceeded(RuntimeError)
+}
+
+impl Verdict {
+    pub fn is_pass(&self) -> bool {
+        *self == Verdict::Pass
+    }
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Verdict::Pass => write!(f, "pass"),
+            Verdict::ForbiddenInstruction{instruction, operation} =>
+                write!(f, "fail: instruction {} ({:?}) is not allowed in this level", instruction, operation),
+            Verdict::WrongOutbox{position, expected, actual} =>
+                write!(f, "fail: outbox item {} should be {}, got {}", position, describe_value(expected, "nothing"), describe_value(actual, "nothing")),
+            Verdict::RuntimeError(ref error) => write!(f, "fail: {}", error),
+            Verdict::LimitExceeded(ref error) => write!(f, "fail: {}", error)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Verification {
+    pub verdict: Verdict,
+    // None when the program was rejected before running it
+    pub outcome: Option<RunOutcome>,
+    pub final_state: InternalState
+}
+
+impl Level {
+    // what the game sets up when you press play, its letter arithmetic included
+    pub fn initial_state(&self) -> InternalState {
+        self.state_with_inbox(&self.inbox)
+            .with_expected_output(Some(self.expected_outbox.clone()))
+            .with_semantics(Semantics::Game)
+    }
+
+    // the floor of the level, with other items in the inbox
+    pub fn state_with_inbox(&self, inbox: &[Value]) -> InternalState {
+        InternalState::new(None, 0)
+            .with_input_tape(inbox.iter().rev().cloned().collect())
+            .with_memory(self.memory.clone())
+            .with_empty_tiles_up_to(self.floor_size)
+    }
+
+    pub fn allows(&self, operation: Operation) -> bool {
+        match (&self.allowed_instructions, game_name(operation)) {
+            (Some(allowed), Some(name)) => allowed.iter().any(|allowed| allowed.eq_ignore_ascii_case(name)),
+            _ => true
+        }
+    }
+}
+
+// the first item differing from the expected outbox
+pub fn first_mismatch(expected_outbox: &[Value], outbox: &[Value]) -> Option<(usize, Option<Value>, Option<Value>)> {
+    let length = outbox.len().max(expected_outbox.len());
+    (0..length)
+        .map(|position| (position, expected_outbox.get(position).cloned(), outbox.get(position).cloned()))
+        .find(|&(_, expected, actual)| expected != actual)
+}
+
+// without an expected outbox, any completed run passes
+pub fn judge(outcome: &RunOutcome, outbox: &[Value], expected_outbox: Option<&[Value]>) -> Verdict {
+    match *outcome {
+        RunOutcome::RuntimeError(RuntimeError{kind: ErrorKind::WrongOutput{position, expected, actual}, ..}) =>
+            Verdict::WrongOutbox{position, expected, actual: Some(actual)},
+        RunOutcome::RuntimeError(error) => Verdict::RuntimeError(error),
+        RunOutcome::LimitExceeded(error) => Verdict::LimitExceeded(error),
+        _ => match expected_outbox.and_then(|expected_outbox| first_mismatch(expected_outbox, outbox)) {
+            Some((position, expected, actual)) => Verdict::WrongOutbox{position, expected, actual},
+            None => Verdict::Pass
+        }
+    }
+}
+
+pub fn verify(level: &Level, operations: Vec<Operation>) -> Verification {
+    verify_state(level, level.initial_state(), operations, Limits::default())
+}
+
+// like verify, starting from a state prepared by the caller (value range, semantics...)
+pub fn verify_state(level: &Level, mut state: InternalState, operations: Vec<Operation>, limits: Limits) -> Verification {
+    let forbidden = operations.iter().position(|operation| !level.allows(*operation));
+    if let Some(instruction) = forbidden {
+        return Verification {
+            verdict: Verdict::ForbiddenInstruction{instruction, operation: operations[instruction]},
+            outcome: None,
+            final_state: state
+        };
+    }
+
+    // the outbox stops the program as soon as an item is wrong
+    state.expected_output = Some(level.expected_outbox.clone());
+    let outcome = CodeIterator::without_dump(&mut state, operations)
+        .with_limits(limits)
+        .run();
+    let verdict = judge(&outcome, &state.output_tape, Some(&level.expected_outbox));
+
+    Verification{verdict, outcome: Some(outcome), final_state: state}
+}
+
+#[cfg(test)]
+mod test {
+    use Value;
+    use Location;
+    use Operation;
+    use level::{Level, Targets, Verdict, verify, verify_state};
+    use limits::Limits;
+    use state::Semantics;
+    use error::ErrorKind;
+
+    fn level() -> Level {
+        Level {
+            name: Some(String::from("Mail Room")),
+            inbox: vec!(Value::Number{value: 1}, Value::Character{value: 'b'}),
+            memory: vec!(),
+            floor_size: 1,
+            allowed_instructions: Some(vec!(String::from("INBOX"), String::from("OUTBOX"), String::from("jump"))),
+            expected_outbox: vec!(Value::Number{value: 1}, Value::Character{value: 'b'}),
+            targets: Targets::default(),
+            generator: None
+        }
+    }
+
+    fn mail_room() -> Vec<Operation> {
+        vec!(Operation::Label, Operation::Inbox, Operation::Outbox, Operation::Jump{next_operation: 0})
+    }
+
+    #[test]
+    fn initial_state() {
+        let state = level().initial_state();
+
+        assert_eq!(state.input_tape, vec!(Value::Character{value: 'b'}, Value::Number{value: 1}));
+        assert_eq!(state.memory, vec!(None));
+    }
+
+    #[test]
+    fn passes() {
+        let verification = verify(&level(), mail_room());
+
+        assert!(verification.verdict.is_pass());
+        assert_eq!(verification.verdict.to_string(), "pass");
+        assert_eq!(verification.final_state.executed_instructions(), 9);
+    }
+
+    #[test]
+    fn forbidden_instruction() {
+        let operations = vec!(Operation::Inbox, Operation::CopyTo{cell: Location::Cell(0)}, Operation::Outbox);
+
+        assert_eq!(verify(&level(), operations).verdict,
+            Verdict::ForbiddenInstruction{instruction: 1, operation: Operation::CopyTo{cell: Location::Cell(0)}});
+    }
+
+    #[test]
+    fn wrong_and_missing_items() {
+        let mut level = level();
+        level.expected_outbox = vec!(Value::Number{value: 1}, Value::Character{value: 'c'});
+
+        assert_eq!(verify(&level, mail_room()).verdict,
+            Verdict::WrongOutbox{position: 1, expected: Some(Value::Character{value: 'c'}), actual: Some(Value::Character{value: 'b'})});
+
+        let only_first = vec!(Operation::Inbox, Operation::Outbox);
+        assert_eq!(verify(&level, only_first.clone()).verdict,
+            Verdict::WrongOutbox{position: 1, expected: Some(Value::Character{value: 'c'}), actual: None});
+        assert_eq!(verify(&level, only_first).verdict.to_string(), "fail: outbox item 1 should be c, got nothing");
+    }
+
+    #[test]
+    fn stops_at_the_first_wrong_item() {
+        let mut level = level();
+        level.expected_outbox = vec!(Value::Character{value: 'b'});
+        let verification = verify(&level, mail_room());
+
+        assert_eq!(verification.verdict,
+            Verdict::WrongOutbox{position: 0, expected: Some(Value::Character{value: 'b'}), actual: Some(Value::Number{value: 1})});
+        assert_eq!(verification.final_state.executed_instructions(), 2);
+    }
+
+    #[test]
+    fn game_letter_arithmetic() {
+        // the game cannot add a number to a letter
+        let mut level = level();
+        level.memory = vec!(Some(Value::Number{value: 1}));
+        level.allowed_instructions = None;
+        let operations = vec!(Operation::Inbox, Operation::Inbox, Operation::Add{cell: Location::Cell(0)}, Operation::Outbox);
+
+        assert!(match verify(&level, operations.clone()).verdict {
+            Verdict::RuntimeError(error) => error.kind == ErrorKind::CharArithmetic{
+                register: Value::Character{value: 'b'},
+                operand: Value::Number{value: 1}},
+            _ => false
+        });
+
+        let extended = level.initial_state().with_semantics(Semantics::Extended);
+        assert!(match verify_state(&level, extended, operations, Limits::default()).verdict {
+            Verdict::WrongOutbox{position: 0, ..} => true,
+            _ => false
+        });
+    }
+
+    #[test]
+    fn runtime_error() {
+        let operations = vec!(Operation::Outbox);
+
+        assert!(match verify(&level(), operations).verdict {
+            Verdict::RuntimeError(_) => true,
+            _ => false
+        });
+    }
+}
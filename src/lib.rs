@@ -1,23 +1,61 @@
+// tests check variants with assert!(match ... { ... => true, _ => false })
+#![cfg_attr(test, allow(clippy::match_like_matches_macro))]
+
 #[macro_use]
 extern crate serde_derive;
 extern crate serde;
 extern crate serde_json;
 
-use json::dump_state;
+use trace::{Tracer, Moment, JsonLines, Granularity};
+use std::fmt;
+use std::time::Instant;
 
-#[derive(Serialize, Debug, Clone, Copy)]
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
 pub enum Value {
 	Number{value: i32},
 	Character{value: char}
 }
 
-#[derive(Serialize, Debug, Clone, Copy)]
+impl fmt::Display for Value {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Value::Number{value} => write!(f, "{}", value),
+			Value::Character{value} => write!(f, "{}", value)
+		}
+	}
+}
+
+// missing is what to print for no value: an empty tile, or an item that never came
+pub fn describe_value(value: Option<Value>, missing: &str) -> String {
+	value.map(|value| value.to_string()).unwrap_or_else(|| String::from(missing))
+}
+
+// a number, or a single letter
+pub fn parse_value(raw: &str) -> Result<Value, String> {
+	if let Ok(value) = raw.parse::<i32>() {
+		return Ok(Value::Number{value});
+	}
+
+	let mut chars = raw.chars();
+	match (chars.next(), chars.next()) {
+		(Some(value), None) => Ok(Value::Character{value}),
+		_ => Err(format!("{} is not a number nor a letter", raw))
+	}
+}
+
+// an inbox or an outbox, like [1, a]
+pub fn describe_values(values: &[Value]) -> String {
+	let items: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+	format!("[{}]", items.join(", "))
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
 pub enum Location {
 	Cell(usize),
 	Address(usize)
 }
 
-#[derive(Serialize, Debug, Clone, Copy)]
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
 pub enum Operation {
 	Inbox,
 	Outbox,
@@ -33,61 +71,350 @@ pub enum Operation {
 	BumpMinus{cell: Location},
 }
 
+// a loaded program, with the position of every label in it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+	pub operations: Vec<Operation>,
+	pub labels: Vec<(String, usize)>
+}
+
+impl Program {
+	pub fn label_position(&self, label: &str) -> Option<usize> {
+		self.labels.iter().rev().find(|pair| pair.0 == label).map(|pair| pair.1)
+	}
+
+	pub fn label_at(&self, position: usize) -> Option<&str> {
+		self.labels.iter().find(|pair| pair.1 == position).map(|pair| pair.0.as_str())
+	}
+}
+
+pub mod analysis;
+pub mod coverage;
+pub mod debug;
+pub mod debugger;
+pub mod diff;
+pub mod enumerate;
+pub mod error;
+pub mod fuzz;
+pub mod history;
 pub mod json;
+pub mod level;
+pub mod limits;
 pub mod memory;
 pub mod operators;
+pub mod score;
+pub mod source;
 pub mod state;
+pub mod suite;
+pub mod trace;
+
+// how a run of the program ended
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunOutcome {
+	// ran past the last instruction
+	CompletedAtEnd,
+	// the way programs end in the game
+	CompletedOnEmptyInbox,
+	RuntimeError(error::RuntimeError),
+	LimitExceeded(error::RuntimeError),
+	Breakpoint(debug::Hit)
+}
+
+impl RunOutcome {
+	fn from_error(error: error::RuntimeError) -> Self {
+		match error.kind {
+			error::ErrorKind::EmptyInbox => RunOutcome::CompletedOnEmptyInbox,
+			kind if kind.is_limit() => RunOutcome::LimitExceeded(error),
+			_ => RunOutcome::RuntimeError(error)
+		}
+	}
+
+	pub fn is_completed(&self) -> bool {
+		matches!(*self, RunOutcome::CompletedAtEnd | RunOutcome::CompletedOnEmptyInbox)
+	}
+}
+
+// runs the program until it ends, without hooks nor dumps
+pub fn run(state: &mut state::InternalState, operations: Vec<Operation>) -> RunOutcome {
+	CodeIterator::without_dump(state, operations).run()
+}
+
+// what happened during a single step of CodeIterator
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+	Executed(state::InternalState),
+	// a hook stopped the execution before the next instruction
+	Paused(debug::Hit)
+}
 
 pub struct CodeIterator<'a> {
 	pub state: &'a mut state::InternalState,
 	pub operations: Vec<Operation>,
-	has_errored: bool,
-	dump_file_path: &'a str
+	pub hooks: debug::Hooks,
+	pub history: Option<history::History>,
+	// watchpoints hit by the last instruction, reported one by one before the next one
+	pending_hits: Vec<debug::Hit>,
+	// the error that stopped the program, an empty inbox included
+	stopped_by: Option<error::RuntimeError>,
+	resuming: bool,
+	tracer: Tracer<'a>,
+	pub limits: limits::Limits,
+	// set by the first instruction, for the timeout
+	started: Option<Instant>
 }
 
 impl<'a> CodeIterator<'a> {
-	pub fn new(_state: &'a mut state::InternalState, _operations: Vec<Operation>, dump_file_path: &'a str) -> Self {
-		CodeIterator{state: _state, operations: _operations, has_errored: false, dump_file_path}
+	// appends every state to the dump file, one json object per line
+	pub fn new(_state: &'a mut state::InternalState, _operations: Vec<Operation>, dump_file_path: &str) -> Self {
+		let tracer = match JsonLines::append_to(dump_file_path) {
+			Ok(sink) => Tracer::new(Box::new(sink), Granularity::EveryStep),
+			Err(reason) => Tracer::failed(reason)
+		};
+		CodeIterator{tracer, ..CodeIterator::without_dump(_state, _operations)}
+	}
+
+	// runs the program without writing any dump file
+	pub fn without_dump(_state: &'a mut state::InternalState, _operations: Vec<Operation>) -> Self {
+		CodeIterator{
+			state: _state,
+			operations: _operations,
+			hooks: debug::Hooks::new(vec!()),
+			history: None,
+			pending_hits: vec!(),
+			stopped_by: None,
+			resuming: false,
+			tracer: Tracer::disabled(),
+			limits: limits::Limits::default(),
+			started: None
+		}
+	}
+
+	pub fn with_trace(mut self, tracer: Tracer<'a>) -> Self {
+		self.tracer = tracer;
+		self
+	}
+
+	pub fn with_limits(mut self, limits: limits::Limits) -> Self {
+		self.limits = limits;
+		self
+	}
+
+	fn dump(&mut self, moment: Moment, error: Option<&error::RuntimeError>) {
+		self.tracer.report(moment, self.state, error);
+	}
+
+	// flushes the trace, returning the first error met while writing it
+	pub fn finish_trace(&mut self) -> std::io::Result<()> {
+		self.tracer.finish()
+	}
+
+	// lets hooks refer to labels by name
+	pub fn with_labels(mut self, labels: Vec<(String, usize)>) -> Self {
+		self.hooks.set_labels(labels);
+		self
+	}
+
+	// records every executed instruction so that it can be undone
+	pub fn with_history(mut self) -> Self {
+		self.history = Some(history::History::new());
+		self
+	}
+
+	pub fn has_pending_hit(&self) -> bool {
+		!self.pending_hits.is_empty()
+	}
+
+	// runs until the program ends or a hook pauses it
+	pub fn run(&mut self) -> RunOutcome {
+		loop {
+			match self.next() {
+				Some(Ok(Step::Executed(_))) => (),
+				Some(Ok(Step::Paused(hit))) => return RunOutcome::Breakpoint(hit),
+				Some(Err(error)) => return RunOutcome::from_error(error),
+				None => return self.end_outcome()
+			}
+		}
+	}
+
+	// how the program ended, once next() returned None
+	pub fn end_outcome(&self) -> RunOutcome {
+		match self.stopped_by {
+			Some(error) => RunOutcome::from_error(error),
+			None => RunOutcome::CompletedAtEnd
+		}
+	}
+
+	// undoing an instruction lets the program run again even if it had ended
+	fn rewound(&mut self) {
+		self.stopped_by = None;
+		self.pending_hits.clear();
+		self.resuming = true;
+	}
+
+	pub fn step_back(&mut self) -> Option<history::Delta> {
+		let delta = self.history.as_mut()?.step_back(self.state)?;
+		self.rewound();
+		Some(delta)
+	}
+
+	// moves to a recorded step, forwards or backwards
+	pub fn travel_to(&mut self, step: u32) -> bool {
+		let travelled = match self.history {
+			Some(ref mut history) => history.travel_to(step, self.state),
+			None => false
+		};
+		if travelled {
+			self.rewound();
+		}
+		travelled
 	}
 }
 
 impl<'a> Iterator for CodeIterator<'a> {
-	type Item = Result<state::InternalState, String>;
+	type Item = Result<Step, error::RuntimeError>;
 
 	fn next(&mut self) -> Option<Self::Item> {
-		let srcpath = self.dump_file_path;
-		if self.has_errored {
-			None
+		if self.stopped_by.is_some() {
+			return None;
 		}
-		else if self.state.executed_instructions() > 10000 {
-			self.has_errored = true;
-			let result: Result<(), String> = Err(String::from("instructions limit reached"));
-			dump_state(&self.state, srcpath, &result.err().unwrap());
+		if !self.pending_hits.is_empty() {
+			return Some(Ok(Step::Paused(self.pending_hits.remove(0))));
+		}
+		if self.state.instruction_counter >= self.operations.len() {
+			self.dump(Moment::End, None);
 			return None;
 		}
-		else if self.state.instruction_counter < self.operations.len() {
-			let _operation = self.operations[self.state.instruction_counter];
 
-			let result = self.state.apply(_operation);
+		// after a pause, the instruction runs without checking the hooks again
+		if !self.resuming {
+			if let Some(hit) = self.hooks.before_instruction(self.state) {
+				self.resuming = true;
+				return Some(Ok(Step::Paused(hit)));
+			}
+		}
+		self.resuming = false;
+
+		let _operation = self.operations[self.state.instruction_counter];
+		let started = *self.started.get_or_insert_with(Instant::now);
+		let history_size = self.history.as_ref().map(|history| history.applied());
+		if let Err(kind) = self.limits.check(_operation, self.state, history_size, started) {
+			let error = error::RuntimeError::new(self.state.instruction_counter, _operation, kind);
+			self.stopped_by = Some(error);
+			self.dump(Moment::Error, Some(&error));
+			return Some(Err(error));
+		}
 
-			if result.is_err() {
-				if let Operation::Inbox =  _operation {
-					self.has_errored = false;
-					dump_state(&self.state, srcpath, &result.err().unwrap());
-					return None;
+		let instruction = self.state.instruction_counter;
+		let delta = self.history.as_ref().map(|_| history::Delta::before(_operation, self.state));
+		self.hooks.before_access(_operation, self.state);
+		match self.state.apply(_operation) {
+			Ok(()) => {
+				self.pending_hits = self.hooks.after_access(instruction, _operation, self.state);
+				if let (Some(history), Some(delta)) = (self.history.as_mut(), delta) {
+					history.record(delta.after(_operation, self.state));
+				}
+				self.dump(Moment::Step, None);
+				Some(Ok(Step::Executed(self.state.clone())))
+			},
+			Err(error) => {
+				self.stopped_by = Some(error);
+				// an empty inbox is how programs end in the game
+				if let error::ErrorKind::EmptyInbox = error.kind {
+					self.dump(Moment::End, Some(&error));
+					None
 				}
 				else {
-					dump_state(&self.state, srcpath, &result.err().unwrap());
-					self.has_errored = true;
+					self.dump(Moment::Error, Some(&error));
+					Some(Err(error))
 				}
 			}
-			dump_state(&self.state, srcpath, &String::new());
-
-			Some(Ok(self.state.clone()))
-		}
-		else {
-			dump_state(&self.state, srcpath, &String::new());
-			None
 		}
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use {Value, Location, Operation, CodeIterator, RunOutcome, run, describe_value, describe_values, parse_value};
+	use debug::{Breakpoint, Hit};
+	use error::{RuntimeError, ErrorKind};
+	use limits::Limits;
+	use state::InternalState;
+
+	fn state() -> InternalState {
+		InternalState::new(None, 0)
+			.with_input_tape(vec!(Value::Number{value: 1}))
+			.with_memory(vec!(None))
+	}
+
+	#[test]
+	fn completed_at_end() {
+		let mut state = state();
+
+		assert_eq!(run(&mut state, vec!(Operation::Inbox, Operation::Outbox)), RunOutcome::CompletedAtEnd);
+		assert_eq!(state.output_tape, vec!(Value::Number{value: 1}));
+	}
+
+	#[test]
+	fn completed_on_empty_inbox() {
+		let mut state = state();
+		let outcome = run(&mut state, vec!(Operation::Inbox, Operation::Outbox, Operation::Jump{next_operation: 0}));
+
+		assert_eq!(outcome, RunOutcome::CompletedOnEmptyInbox);
+		assert!(outcome.is_completed());
+	}
+
+	#[test]
+	fn runtime_error() {
+		let mut state = state();
+		let outcome = run(&mut state, vec!(Operation::CopyFrom{cell: Location::Cell(0)}));
+
+		assert_eq!(outcome, RunOutcome::RuntimeError(RuntimeError::new(
+			0, Operation::CopyFrom{cell: Location::Cell(0)}, ErrorKind::EmptyCell{cell: 0})));
+		assert!(!outcome.is_completed());
+	}
+
+	#[test]
+	fn limit_exceeded() {
+		let mut state = state();
+		let outcome = CodeIterator::without_dump(&mut state, vec!(Operation::Label, Operation::Jump{next_operation: 0}))
+			.with_limits(Limits::unlimited().with_max_steps(Some(3)))
+			.run();
+
+		assert_eq!(outcome, RunOutcome::LimitExceeded(RuntimeError::new(
+			1, Operation::Jump{next_operation: 0}, ErrorKind::InstructionsLimitReached{limit: 3})));
+	}
+
+	#[test]
+	fn breakpoint() {
+		let mut state = InternalState::new(None, 0);
+		let mut iterator = CodeIterator::without_dump(&mut state, vec!(Operation::Label, Operation::Jump{next_operation: 0}));
+		let id = iterator.hooks.add_breakpoint(Breakpoint::at_instruction(1));
+
+		assert_eq!(iterator.run(), RunOutcome::Breakpoint(Hit::Breakpoint{id, instruction: 1}));
+	}
+
+	#[test]
+	fn describe() {
+		assert_eq!(describe_value(Some(Value::Character{value: 'a'}), "_"), "a");
+		assert_eq!(describe_value(None, "nothing"), "nothing");
+		assert_eq!(describe_values(&[Value::Number{value: -3}, Value::Character{value: 'b'}]), "[-3, b]");
+		assert_eq!(describe_values(&[]), "[]");
+	}
+
+	#[test]
+	fn parse() {
+		assert_eq!(parse_value("-12"), Ok(Value::Number{value: -12}));
+		assert_eq!(parse_value("a"), Ok(Value::Character{value: 'a'}));
+		assert_eq!(parse_value("ab"), Err(String::from("ab is not a number nor a letter")));
+	}
+
+	#[test]
+	fn labels_keep_the_hooks() {
+		let mut state = state();
+		let mut iterator = CodeIterator::without_dump(&mut state, vec!(Operation::Label, Operation::Inbox));
+		let id = iterator.hooks.add_breakpoint(Breakpoint::at_label("start"));
+		let mut iterator = iterator.with_labels(vec!((String::from("start"), 0)));
+
+		assert_eq!(iterator.run(), RunOutcome::Breakpoint(Hit::Breakpoint{id, instruction: 0}));
+	}
+}
@@ -0,0 +1,145 @@
+use std::time::{Duration, Instant};
+
+use Operation;
+use state::InternalState;
+use error::ErrorKind;
+
+// how far CodeIterator lets a program go before stopping it; None means no limit
+
+pub const DEFAULT_MAX_STEPS: u32 = 10000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    // executed instructions
+    pub max_steps: Option<u32>,
+    // items in the outbox
+    pub max_output: Option<usize>,
+    pub timeout: Option<Duration>,
+    // instructions recorded in the history, when it is kept
+    pub max_history: Option<usize>
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits{max_steps: Some(DEFAULT_MAX_STEPS), max_output: None, timeout: None, max_history: None}
+    }
+}
+
+impl Limits {
+    pub fn unlimited() -> Self {
+        Limits{max_steps: None, max_output: None, timeout: None, max_history: None}
+    }
+
+    pub fn with_max_steps(mut self, max_steps: Option<u32>) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    pub fn with_max_output(mut self, max_output: Option<usize>) -> Self {
+        self.max_output = max_output;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_max_history(mut self, max_history: Option<usize>) -> Self {
+        self.max_history = max_history;
+        self
+    }
+
+    // checked before running the operation: it only runs if no limit would be passed
+    pub fn check(&self, operation: Operation, s: &InternalState, history_size: Option<usize>, started: Instant) -> Result<(), ErrorKind> {
+        if let Some(limit) = self.max_steps {
+            if s.executed_instructions() >= limit {
+                return Err(ErrorKind::InstructionsLimitReached{limit});
+            }
+        }
+        if let Some(limit) = self.max_output {
+            if operation == Operation::Outbox && s.output_tape.len() >= limit {
+                return Err(ErrorKind::OutputLimitReached{limit});
+            }
+        }
+        if let (Some(limit), Some(size)) = (self.max_history, history_size) {
+            if size >= limit {
+                return Err(ErrorKind::HistoryLimitReached{limit});
+            }
+        }
+        if let Some(timeout) = self.timeout {
+            if started.elapsed() >= timeout {
+                return Err(ErrorKind::TimeoutReached{milliseconds: timeout.as_millis() as u64});
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use Value;
+    use Location;
+    use Operation;
+    use CodeIterator;
+    use error::ErrorKind;
+    use limits::Limits;
+    use state::InternalState;
+    use std::time::{Duration, Instant};
+
+    fn endless_program() -> Vec<Operation> {
+        vec!(
+            Operation::BumpPlus{cell: Location::Cell(0)},
+            Operation::Outbox,
+            Operation::Jump{next_operation: 0}
+        )
+    }
+
+    fn stop_reason(limits: Limits, with_history: bool) -> Option<ErrorKind> {
+        let mut state = InternalState::new(None, 0)
+            .with_memory(vec!(Some(Value::Number{value: 0})))
+            .with_value_range(None);
+        let mut iterator = CodeIterator::without_dump(&mut state, endless_program())
+            .with_limits(limits);
+        if with_history {
+            iterator = iterator.with_history();
+        }
+
+        let stopped = iterator.filter_map(|step| step.err()).next();
+        stopped.map(|error| error.kind)
+    }
+
+    #[test]
+    fn check_steps_and_output() {
+        let mut s = InternalState::new(Some(Value::Number{value: 1}), 0);
+        let limits = Limits::unlimited().with_max_steps(Some(1)).with_max_output(Some(0));
+
+        assert_eq!(limits.check(Operation::Outbox, &s, None, Instant::now()), Err(ErrorKind::OutputLimitReached{limit: 0}));
+        assert_eq!(limits.check(Operation::Inbox, &s, None, Instant::now()), Ok(()));
+        s.increase_executed_instructions();
+        assert_eq!(limits.check(Operation::Inbox, &s, None, Instant::now()), Err(ErrorKind::InstructionsLimitReached{limit: 1}));
+    }
+
+    #[test]
+    fn each_limit_stops_the_program() {
+        assert_eq!(stop_reason(Limits::default(), false), Some(ErrorKind::InstructionsLimitReached{limit: 10000}));
+        assert_eq!(stop_reason(Limits::unlimited().with_max_output(Some(5)), false), Some(ErrorKind::OutputLimitReached{limit: 5}));
+        assert_eq!(stop_reason(Limits::unlimited().with_max_history(Some(20)), true), Some(ErrorKind::HistoryLimitReached{limit: 20}));
+        assert_eq!(stop_reason(Limits::unlimited().with_timeout(Some(Duration::from_millis(1))), false),
+            Some(ErrorKind::TimeoutReached{milliseconds: 1}));
+    }
+
+    #[test]
+    fn steps_limit_is_exact() {
+        let mut state = InternalState::new(None, 0)
+            .with_memory(vec!(Some(Value::Number{value: 0})));
+        {
+            let iterator = CodeIterator::without_dump(&mut state, endless_program())
+                .with_limits(Limits::unlimited().with_max_steps(Some(7)));
+            for _ in iterator {}
+        }
+
+        assert_eq!(state.executed_instructions(), 7);
+    }
+}
@@ -1,59 +1,643 @@
 extern crate clap;
 
 extern crate hrm_interpreter;
-use hrm_interpreter::json::{read_file, read_config};
-use hrm_interpreter::CodeIterator;
-use clap::{Arg, App};
+use hrm_interpreter::json::{read_program_file, read_config, read_level, read_generator};
+use hrm_interpreter::level::{verify_state, Verdict, Level};
+use hrm_interpreter::score::score;
+use hrm_interpreter::suite::{TestCase, cases_from_directory, cases_from_manifest, run_tests};
+use hrm_interpreter::diff::compare_cases;
+use hrm_interpreter::enumerate::{Domain, find_counterexample};
+use hrm_interpreter::fuzz::{Generator, fuzz, completes, agrees_with, inboxes, with_inbox};
+use hrm_interpreter::coverage::coverage;
+use hrm_interpreter::source;
+use hrm_interpreter::analysis::cfg::ControlFlowGraph;
+use hrm_interpreter::analysis::reachability::dead_code;
+use hrm_interpreter::analysis::initialization::empty_reads;
+use hrm_interpreter::analysis::types::type_problems;
+use hrm_interpreter::{CodeIterator, Operation, Program, RunOutcome};
+use hrm_interpreter::debugger::Debugger;
+use hrm_interpreter::error::LoadError;
+use hrm_interpreter::state::{InternalState, Semantics};
+use hrm_interpreter::trace::{Tracer, Granularity, JsonLines};
+use hrm_interpreter::limits::{Limits, DEFAULT_MAX_STEPS};
+use clap::{Arg, App, ArgGroup, ArgMatches, SubCommand};
+use std::io;
+use std::process;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-fn main() {
-    let app_data = App::new("hrm-interpreter")
-        .version("0.1")
-        .arg(Arg::with_name("code")
-            .short("c")
-            .long("code")
-            .value_name("CODE")
-            .takes_value(true))
-        .arg(Arg::with_name("input")
-            .short("i")
-            .long("input")
-            .value_name("INPUT")
-            .takes_value(true))
-        .arg(Arg::with_name("dump")
-            .short("d")
-            .long("dump")
-            .value_name("DUMP")
-            .takes_value(true));
+fn exit_with_load_errors(path: &str, errors: &[LoadError]) -> ! {
+    for error in errors {
+        // positioned errors already start with "line:column:"
+        if error.line.is_some() {
+            eprintln!("{}:{}", path, error);
+        }
+        else {
+            eprintln!("{}: {}", path, error);
+        }
+    }
+    eprintln!("{} error(s) found while loading {}", errors.len(), path);
+    process::exit(1);
+}
 
-    let matches = app_data.get_matches();
+fn code_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("code")
+        .short("c")
+        .long("code")
+        .value_name("CODE")
+        .takes_value(true)
+}
+
+fn semantics_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("semantics")
+        .long("semantics")
+        .value_name("SEMANTICS")
+        .possible_values(&["game", "extended"])
+        .help("'game' rejects the letter arithmetic the game does not allow; levels default to 'game', everything else to 'extended'")
+}
+
+// arguments shared by every way of running a program
+fn program_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec!(
+        code_arg(),
+        Arg::with_name("unbounded")
+            .long("unbounded")
+            .help("allows numbers outside of the -999..999 range of the game"),
+        semantics_arg(),
+        Arg::with_name("max-steps")
+            .long("max-steps")
+            .value_name("STEPS")
+            .help("stops the program after this many instructions, 'none' lifts the limit (default 10000)"),
+        Arg::with_name("max-output")
+            .long("max-output")
+            .value_name("ITEMS")
+            .help("stops the program when it tries to put more items than this in the outbox"),
+        Arg::with_name("timeout")
+            .long("timeout")
+            .value_name("MILLISECONDS")
+            .help("stops the program after running for this long")
+    )
+}
+
+fn parse_limit<T: FromStr>(matches: &ArgMatches, name: &str) -> Option<T> {
+    matches.value_of(name).map(|raw| match raw.parse::<T>() {
+        Ok(limit) => limit,
+        Err(_) => {
+            eprintln!("--{} expects a number, not {}", name, raw);
+            process::exit(1);
+        }
+    })
+}
+
+fn load_limits(matches: &ArgMatches) -> Limits {
+    let max_steps = match matches.value_of("max-steps") {
+        Some("none") => None,
+        Some(_) => parse_limit(matches, "max-steps"),
+        None => Some(DEFAULT_MAX_STEPS)
+    };
+
+    Limits::default()
+        .with_max_steps(max_steps)
+        .with_max_output(parse_limit(matches, "max-output"))
+        .with_timeout(parse_limit(matches, "timeout").map(Duration::from_millis))
+        .with_max_history(parse_limit(matches, "max-history"))
+}
+
+fn load_program(srcpath: &str) -> Program {
+    // .hrm files are copied straight from the game, everything else comes from hrm-compiler
+    let loaded_code = if srcpath.ends_with(".hrm") {
+        source::read_program_file(String::from(srcpath))
+    }
+    else {
+        read_program_file(String::from(srcpath))
+    };
+
+    match loaded_code {
+        Ok(program) => program,
+        Err(errors) => exit_with_load_errors(srcpath, &errors)
+    }
+}
+
+fn input_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("input")
+        .short("i")
+        .long("input")
+        .value_name("INPUT")
+        .takes_value(true)
+}
+
+fn load_state(inputpath: &str, matches: &ArgMatches) -> InternalState {
+    let internal_state = match read_config(String::from(inputpath)) {
+        Ok(internal_state) => internal_state,
+        Err(error) => exit_with_load_errors(inputpath, &[error])
+    };
+    configure_state(internal_state, matches)
+}
+
+fn configure_state(mut internal_state: InternalState, matches: &ArgMatches) -> InternalState {
+    if matches.is_present("unbounded") {
+        internal_state.value_range = None;
+    }
+    // without --semantics, the state keeps its own
+    match matches.value_of("semantics") {
+        Some("game") => internal_state.semantics = Semantics::Game,
+        Some("extended") => internal_state.semantics = Semantics::Extended,
+        _ => ()
+    }
+
+    internal_state
+}
+
+fn parse_granularity(raw: &str) -> Granularity {
+    match raw {
+        "step" => Granularity::EveryStep,
+        "error" => Granularity::OnError,
+        "final" => Granularity::Final,
+        steps => match steps.parse::<u32>() {
+            Ok(steps) if steps > 0 => Granularity::EveryNSteps(steps),
+            _ => {
+                eprintln!("granularity must be step, error, final or a number of steps, not {}", steps);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+// "-" writes the states on the standard output
+fn create_tracer<'a>(dumppath: Option<&str>, granularity: Granularity) -> Tracer<'a> {
+    match dumppath {
+        Some("-") => Tracer::new(Box::new(JsonLines::stdout()), granularity),
+        Some(path) => match JsonLines::append_to(path) {
+            Ok(sink) => Tracer::new(Box::new(sink), granularity),
+            Err(reason) => Tracer::failed(reason)
+        },
+        None => Tracer::disabled()
+    }
+}
+
+fn run(matches: &ArgMatches) {
     let srcpath = matches.value_of("code").unwrap();
     let inputpath = matches.value_of("input").unwrap();
-    let dumppath = matches.value_of("dump").unwrap();
+    let dumppath = matches.value_of("dump");
+    let granularity = parse_granularity(matches.value_of("granularity").unwrap());
 
-    let code = read_file(String::from(srcpath));
+    let code = load_program(srcpath).operations;
     // create the state to be modified
-    let mut internal_state = read_config(String::from(inputpath));
-
-    let mut errored = false;
-    let mut reason = String::new();
-    {
-        let code_execution = CodeIterator::new(&mut internal_state, code, dumppath);
-
-        for operation_result in code_execution {
-            if operation_result.is_err() {
-                errored = true;
-                reason = operation_result.err().unwrap();
-                break;
-            }
+    let mut internal_state = load_state(inputpath, matches);
+
+    let outcome = {
+        let mut code_execution = CodeIterator::without_dump(&mut internal_state, code)
+            .with_trace(create_tracer(dumppath, granularity))
+            .with_limits(load_limits(matches));
+
+        let outcome = code_execution.run();
+        if let Err(reason) = code_execution.finish_trace() {
+            eprintln!("cannot write the dump: {}", reason);
+            process::exit(1);
         }
+        outcome
+    };
+
+    match outcome {
+        RunOutcome::RuntimeError(ref reason) | RunOutcome::LimitExceeded(ref reason) => {
+            println!("!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!");
+            println!("Error: {}", reason);
+            println!("Dumping current internal state:");
+            println!("{:?}", internal_state);
+        },
+        _ => ()
     }
+    process::exit(exit_code(&outcome));
+}
 
-    if errored {
-        println!("!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!");
-        println!("Error: {}", reason);
-        println!("Dumping current internal state:");
-        println!("{:?}", internal_state);
+// 1 is used for files that cannot be loaded
+fn exit_code(outcome: &RunOutcome) -> i32 {
+    match *outcome {
+        RunOutcome::CompletedOnEmptyInbox | RunOutcome::CompletedAtEnd => 0,
+        RunOutcome::RuntimeError(_) => 3,
+        RunOutcome::LimitExceeded(_) => 4,
+        RunOutcome::Breakpoint(_) => 5
+    }
+}
+
+fn debug(matches: &ArgMatches) {
+    let srcpath = matches.value_of("code").unwrap();
+    let inputpath = matches.value_of("input").unwrap();
+
+    let program = load_program(srcpath);
+    let mut internal_state = load_state(inputpath, matches);
+
+    let code_execution = CodeIterator::without_dump(&mut internal_state, program.operations)
+        .with_labels(program.labels)
+        .with_history()
+        .with_limits(load_limits(matches));
+    let mut debugger = Debugger::new(code_execution);
+    println!("debugging {}, type help for the list of commands", srcpath);
+
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    if let Err(reason) = debugger.run(stdin.lock(), &mut stdout.lock()) {
+        eprintln!("debugger stopped: {}", reason);
+        process::exit(1);
+    }
+}
+
+fn level_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("level")
+        .short("l")
+        .long("level")
+        .value_name("LEVEL")
+        .takes_value(true)
+        .required(true)
+}
+
+fn load_level(levelpath: &str) -> Level {
+    match read_level(levelpath) {
+        Ok(level) => level,
+        Err(error) => exit_with_load_errors(levelpath, &[error])
+    }
+}
+
+fn verify(matches: &ArgMatches) {
+    let srcpath = matches.value_of("code").unwrap();
+    let levelpath = matches.value_of("level").unwrap();
+
+    let code = load_program(srcpath).operations;
+    let level = load_level(levelpath);
+    let internal_state = configure_state(level.initial_state(), matches);
+
+    let verification = verify_state(&level, internal_state, code, load_limits(matches));
+    println!("{}", verification.verdict);
+    process::exit(match verification.verdict {
+        Verdict::Pass => 0,
+        Verdict::RuntimeError(_) => 3,
+        Verdict::LimitExceeded(_) => 4,
+        Verdict::ForbiddenInstruction{..} | Verdict::WrongOutbox{..} => 6
+    });
+}
+
+fn score_program(matches: &ArgMatches) {
+    let srcpath = matches.value_of("code").unwrap();
+    let level = load_level(matches.value_of("level").unwrap());
+
+    let code = load_program(srcpath).operations;
+    // without other inputs, the level inbox is enough
+    let states = match matches.values_of("input") {
+        Some(inputpaths) => inputpaths.map(|inputpath| load_state(inputpath, matches)).collect(),
+        None => vec!(configure_state(level.initial_state(), matches))
+    };
+
+    let report = score(&code, states, level.targets, load_limits(matches));
+    print!("{}", report);
+    process::exit(if report.all_completed() { 0 } else { 3 });
+}
+
+// the inputs of test and diff: a directory, a manifest or input files
+fn case_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec!(
+        input_arg()
+            .multiple(true)
+            .number_of_values(1)
+            .help("input file, can be repeated"),
+        Arg::with_name("dir")
+            .long("dir")
+            .value_name("DIRECTORY")
+            .help("runs every .json input file of the directory"),
+        Arg::with_name("manifest")
+            .long("manifest")
+            .value_name("MANIFEST")
+            .help("json list of cases, each an input file and an optional name")
+    )
+}
+
+// where random inboxes come from, for fuzz and coverage
+fn generator_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec!(
+        level_arg()
+            .required(false)
+            .help("level giving the floor and the generator"),
+        Arg::with_name("generator")
+            .short("g")
+            .long("generator")
+            .value_name("GENERATOR")
+            .help("json file describing the inboxes, instead of the generator of the level"),
+        Arg::with_name("floor")
+            .long("floor")
+            .value_name("INPUT")
+            .help("input file giving the floor, instead of the one of the level"),
+        Arg::with_name("runs")
+            .long("runs")
+            .value_name("RUNS")
+            .help("number of random inboxes (default 1000)"),
+        Arg::with_name("seed")
+            .long("seed")
+            .value_name("SEED")
+            .help("seed of the random inboxes, to run the same ones again")
+    )
+}
+
+fn cases_group() -> ArgGroup<'static> {
+    ArgGroup::with_name("cases")
+        .args(&["input", "dir", "manifest"])
+        .required(true)
+}
+
+fn load_cases(matches: &ArgMatches) -> Vec<TestCase> {
+    let loaded = match (matches.values_of("input"), matches.value_of("dir"), matches.value_of("manifest")) {
+        (Some(inputpaths), _, _) => Ok(inputpaths
+            .map(|inputpath| TestCase{name: String::from(inputpath), state: load_state(inputpath, matches)})
+            .collect()),
+        (_, Some(directory), _) => cases_from_directory(directory),
+        (_, _, Some(manifest)) => cases_from_manifest(manifest),
+        _ => unreachable!()
+    };
+
+    match loaded {
+        Ok(cases) => cases.into_iter()
+            .map(|case| TestCase{state: configure_state(case.state, matches), ..case})
+            .collect(),
+        Err((path, error)) => exit_with_load_errors(&path, &[error])
+    }
+}
+
+fn test_program(matches: &ArgMatches) {
+    let srcpath = matches.value_of("code").unwrap();
+    let code = load_program(srcpath).operations;
+
+    let cases = load_cases(matches);
+    let threads = parse_limit(matches, "threads").unwrap_or(1);
+
+    let report = run_tests(&code, cases, load_limits(matches), threads);
+    match matches.value_of("format") {
+        Some("junit") => print!("{}", report.to_junit(srcpath)),
+        Some("json") => println!("{}", report.to_json()),
+        _ => print!("{}", report.to_table())
+    }
+    process::exit(if report.all_passed() { 0 } else { 6 });
+}
+
+fn diff_programs(matches: &ArgMatches) {
+    let original = load_program(matches.value_of("code").unwrap()).operations;
+    let optimized = load_program(matches.value_of("optimized").unwrap()).operations;
+    if matches.is_present("enumerate") {
+        enumerate_inboxes(matches, &original, &optimized);
+    }
+    let cases = load_cases(matches);
+
+    let report = compare_cases(&original, &optimized, &cases, load_limits(matches));
+    print!("{}", report);
+    process::exit(if report.is_equivalent() { 0 } else { 7 });
+}
+
+fn enumerate_inboxes(matches: &ArgMatches, original: &[Operation], optimized: &[Operation]) -> ! {
+    let max_length = parse_limit(matches, "enumerate").unwrap();
+    let domain = match Domain::parse(matches.value_of("domain").unwrap()) {
+        Ok(domain) => domain,
+        Err(reason) => {
+            eprintln!("--domain: {}", reason);
+            process::exit(1);
+        }
+    };
+    // only the floor of the file is used
+    let floor = load_floor(matches, None);
+
+    let exhaustion = find_counterexample(original, optimized, &floor, &domain, max_length, load_limits(matches));
+    match exhaustion.counterexample {
+        Some(counterexample) => {
+            println!("counterexample found after {} inboxes", exhaustion.tried);
+            println!("{}", counterexample.comparison);
+            process::exit(7);
+        },
+        None => {
+            println!("equivalent on all {} inboxes of up to {} items", exhaustion.tried, max_length);
+            process::exit(0);
+        }
+    }
+}
+
+// the level floor, the one of an input file, or an empty one
+fn load_floor(matches: &ArgMatches, level: Option<&Level>) -> InternalState {
+    match (level, matches.value_of("floor")) {
+        (_, Some(floorpath)) => load_state(floorpath, matches),
+        (Some(level), None) => configure_state(level.state_with_inbox(&[]), matches),
+        (None, None) => configure_state(InternalState::new(None, 0), matches)
+    }
+}
+
+// the generator given with --generator, or the one of the level
+fn load_generator(matches: &ArgMatches, level: Option<&Level>) -> Generator {
+    match (matches.value_of("generator"), level.and_then(|level| level.generator.clone())) {
+        (Some(generatorpath), _) => match read_generator(generatorpath) {
+            Ok(generator) => generator,
+            Err(error) => exit_with_load_errors(generatorpath, &[error])
+        },
+        (None, Some(generator)) => generator,
+        (None, None) => {
+            eprintln!("the level has no generator, give one with --generator");
+            process::exit(1);
+        }
+    }
+}
+
+// printed with the results, to run the same inboxes again
+fn parse_seed(matches: &ArgMatches) -> u64 {
+    parse_limit(matches, "seed").unwrap_or_else(|| {
+        SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_nanos() as u64).unwrap_or(1)
+    })
+}
+
+fn fuzz_program(matches: &ArgMatches) {
+    let code = load_program(matches.value_of("code").unwrap()).operations;
+    let level = matches.value_of("level").map(load_level);
+    let generator = load_generator(matches, level.as_ref());
+    let floor = load_floor(matches, level.as_ref());
+    let runs = parse_limit(matches, "runs").unwrap_or(1000);
+    let seed = parse_seed(matches);
+    let limits = load_limits(matches);
+
+    let report = match matches.value_of("reference") {
+        Some(referencepath) => {
+            let reference = load_program(referencepath).operations;
+            fuzz(&generator, runs, seed, agrees_with(&reference, &code, &floor, limits))
+        },
+        None => fuzz(&generator, runs, seed, completes(&code, &floor, limits))
+    };
+    print!("{}", report);
+    process::exit(if report.failure.is_none() { 0 } else { 6 });
+}
+
+// runs the program on the given inputs, or on random inboxes, counting what it executes
+fn coverage_program(matches: &ArgMatches) {
+    let srcpath = matches.value_of("code").unwrap();
+    let program = load_program(srcpath);
+    let limits = load_limits(matches);
+
+    let states: Vec<InternalState> = if matches.is_present("cases") {
+        load_cases(matches).into_iter().map(|case| case.state).collect()
     }
     else {
+        let level = matches.value_of("level").map(load_level);
+        let generator = load_generator(matches, level.as_ref());
+        let floor = load_floor(matches, level.as_ref());
+        let runs = parse_limit(matches, "runs").unwrap_or(1000);
+        let seed = parse_seed(matches);
+        eprintln!("{} random inboxes (seed {})", runs, seed);
+        inboxes(&generator, runs, seed).iter().map(|inbox| with_inbox(&floor, inbox)).collect()
+    };
+
+    let coverage = coverage(&program.operations, states, limits);
+    match matches.value_of("format") {
+        Some("json") => println!("{}", coverage.to_json(&program)),
+        Some("lcov") => print!("{}", coverage.to_lcov(&program, srcpath)),
+        _ => print!("{}", coverage.to_listing(&program))
+    }
+}
+
+fn print_cfg(matches: &ArgMatches) {
+    let program = load_program(matches.value_of("code").unwrap());
+    print!("{}", ControlFlowGraph::from_program(&program));
+}
+
+// warnings found without running the program
+fn analyze(matches: &ArgMatches) {
+    let program = load_program(matches.value_of("code").unwrap());
+    let level = matches.value_of("level").map(load_level);
+    let floor = load_floor(matches, level.as_ref());
+
+    print!("{}", dead_code(&program));
+    for empty_read in empty_reads(&program.operations, &floor) {
+        println!("{}", empty_read);
     }
+    for problem in type_problems(&program.operations, &floor) {
+        println!("{}", problem);
+    }
+}
 
+fn main() {
+    let app_data = App::new("hrm-interpreter")
+        .version("0.1")
+        .args(&program_args())
+        .arg(input_arg())
+        .arg(Arg::with_name("dump")
+            .short("d")
+            .long("dump")
+            .value_name("DUMP")
+            .takes_value(true)
+            .help("file the states are appended to, one json object per line; - for the standard output"))
+        .arg(Arg::with_name("granularity")
+            .long("granularity")
+            .value_name("GRANULARITY")
+            .default_value("step")
+            .help("states to dump: step (all), error, final, or one every N steps"))
+        .subcommand(SubCommand::with_name("debug")
+            .about("runs the program step by step in an interactive debugger")
+            .args(&program_args())
+            .arg(input_arg())
+            .arg(Arg::with_name("max-history")
+                .long("max-history")
+                .value_name("STEPS")
+                .help("stops the program when the history to go back in time holds this many instructions")))
+        .subcommand(SubCommand::with_name("verify")
+            .about("runs the program on a level and checks the outbox, like the game does")
+            .args(&program_args())
+            .arg(level_arg()))
+        .subcommand(SubCommand::with_name("score")
+            .about("grades the size of the program and the steps it takes, like the game challenges")
+            .args(&program_args())
+            .arg(level_arg())
+            .arg(input_arg()
+                .multiple(true)
+                .number_of_values(1)
+                .help("input file to average the steps on, can be repeated; the level inbox by default")))
+        .subcommand(SubCommand::with_name("test")
+            .about("runs the program on many inputs, checking their expected_output")
+            .args(&program_args())
+            .args(&case_args())
+            .group(cases_group())
+            .arg(Arg::with_name("threads")
+                .short("j")
+                .long("threads")
+                .value_name("THREADS")
+                .help("number of cases run at the same time (default 1)"))
+            .arg(Arg::with_name("format")
+                .long("format")
+                .value_name("FORMAT")
+                .possible_values(&["table", "junit", "json"])
+                .default_value("table")
+                .help("how the results are printed")))
+        .subcommand(SubCommand::with_name("diff")
+            .about("runs the program and an optimized version of it on the same inputs, checking they behave the same")
+            .args(&program_args())
+            .arg(Arg::with_name("optimized")
+                .short("o")
+                .long("optimized")
+                .value_name("OPTIMIZED")
+                .required(true)
+                .help("the optimized version of the program given with --code"))
+            .args(&case_args())
+            .arg(Arg::with_name("enumerate")
+                .long("enumerate")
+                .value_name("LENGTH")
+                .help("runs the programs on every inbox of up to LENGTH items taken from --domain"))
+            .arg(Arg::with_name("domain")
+                .long("domain")
+                .value_name("VALUES")
+                .default_value("-5..5,a,b")
+                .allow_hyphen_values(true)
+                .help("values of the enumerated inboxes: numbers, letters and ranges separated by commas"))
+            .arg(Arg::with_name("floor")
+                .long("floor")
+                .value_name("INPUT")
+                .requires("enumerate")
+                .help("input file giving the floor of the enumerated inboxes"))
+            .group(cases_group().arg("enumerate")))
+        .subcommand(SubCommand::with_name("fuzz")
+            .about("runs the program on random inboxes, shrinking the first one it fails on")
+            .args(&program_args())
+            .args(&generator_args())
+            .arg(Arg::with_name("reference")
+                .short("r")
+                .long("reference")
+                .value_name("REFERENCE")
+                .help("program whose behaviour the fuzzed one must match; otherwise it must only avoid errors")))
+        .subcommand(SubCommand::with_name("coverage")
+            .about("counts how often each instruction runs and which ways the conditional jumps go, over many inputs")
+            .args(&program_args())
+            .args(&case_args())
+            .args(&generator_args())
+            .group(cases_group().required(false))
+            .arg(Arg::with_name("format")
+                .long("format")
+                .value_name("FORMAT")
+                .possible_values(&["text", "json", "lcov"])
+                .default_value("text")
+                .help("how the counts are printed")))
+        .subcommand(SubCommand::with_name("cfg")
+            .about("prints the basic blocks of the program and the jumps between them")
+            .arg(code_arg().required(true)))
+        .subcommand(SubCommand::with_name("analyze")
+            .about("looks for problems in the program without running it")
+            .arg(code_arg().required(true))
+            .arg(semantics_arg())
+            .arg(level_arg()
+                .required(false)
+                .help("level giving the floor the program starts with"))
+            .arg(Arg::with_name("floor")
+                .long("floor")
+                .value_name("INPUT")
+                .help("input file giving the floor the program starts with, instead of an empty one")));
+
+    let matches = app_data.get_matches();
+    match matches.subcommand() {
+        ("debug", Some(debug_matches)) => debug(debug_matches),
+        ("verify", Some(verify_matches)) => verify(verify_matches),
+        ("score", Some(score_matches)) => score_program(score_matches),
+        ("test", Some(test_matches)) => test_program(test_matches),
+        ("diff", Some(diff_matches)) => diff_programs(diff_matches),
+        ("fuzz", Some(fuzz_matches)) => fuzz_program(fuzz_matches),
+        ("coverage", Some(coverage_matches)) => coverage_program(coverage_matches),
+        ("cfg", Some(cfg_matches)) => print_cfg(cfg_matches),
+        ("analyze", Some(analyze_matches)) => analyze(analyze_matches),
+        _ => run(&matches)
+    }
 }
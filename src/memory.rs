@@ -1,35 +1,36 @@
 use Location;
 use Value;
 use state::InternalState;
+use error::ErrorKind;
 
-#[derive(Debug)]
-pub enum Error {
-    PointerCellContainsChar(Location, Value),
-    NoValue(Location)
+fn check_bounds(cell: usize, s: &InternalState) -> Result<usize, ErrorKind> {
+    if cell < s.floor_size() {
+        Ok(cell)
+    }
+    else {
+        Err(ErrorKind::CellOutOfRange{cell, floor_size: s.floor_size()})
+    }
 }
 
-pub fn extract_memory_position(cell: Location, s: &InternalState) -> Result<usize, Error> {
+// returns the floor tile an operation works on, following pointers
+// and rejecting tiles that are not on the floor, like the game does.
+pub fn extract_memory_position(cell: Location, s: &InternalState) -> Result<usize, ErrorKind> {
     match cell {
-        Location::Cell(mempos) => Ok(mempos),
+        Location::Cell(mempos) => check_bounds(mempos, s),
         Location::Address(mempos) => {
-            let value_from_memory = s.memory[mempos].clone();
-            match value_from_memory {
-                Some(Value::Number{value: pointed_cell}) => Ok(pointed_cell as usize),
-                Some(Value::Character{value: _}) =>
-                    Err(Error::PointerCellContainsChar(cell, value_from_memory.unwrap())),
-                None => Err(Error::NoValue(Location::Cell(mempos)))
+            match s.memory[check_bounds(mempos, s)?] {
+                Some(Value::Number{value: pointed_cell}) => {
+                    if pointed_cell >= 0 && (pointed_cell as usize) < s.floor_size() {
+                        Ok(pointed_cell as usize)
+                    }
+                    else {
+                        Err(ErrorKind::PointerOutOfRange{cell: mempos, pointer: pointed_cell, floor_size: s.floor_size()})
+                    }
+                },
+                Some(Value::Character{value}) =>
+                    Err(ErrorKind::PointerCellContainsChar{cell: mempos, value}),
+                None => Err(ErrorKind::EmptyCell{cell: mempos})
             }
         }
     }
 }
-
-pub fn explain(error: Error) -> String {
-    match error {
-        Error::NoValue(Location::Cell(_cell)) =>
-            format!("There is no value at cell {:?}", _cell),
-        Error::NoValue(Location::Address(_cell)) =>
-            format!("There is no value at cell {:?}", _cell),
-        Error::PointerCellContainsChar(cell, value) =>
-            format!("Cell {:?} should contain a number, not a char({:?})", cell, value)
-    }
-}
@@ -2,50 +2,34 @@ use operators::Operator;
 use Value;
 use Location;
 use state;
+use state::Semantics;
 use memory;
+use error::ErrorKind;
+use operators::letters;
 // --
 use std::char;
 
-enum Error {
-	NoValue{cell: Location},
-	NoEmployeeValue,
-	SumOfChars,
-	SumOverflow{character: char, number: i32}
-}
-
 pub struct AddOp {
 	pub cell: Location
 }
 
 impl AddOp {
-	fn add_number_and_char(num: i32, c: char) -> Result<char, i32> {
-		const ALPHABET_RADIX: u32 = 36;
+	// only allowed by Semantics::Extended; overflow names the register and the operand
+	fn add_number_and_char(num: i32, c: char, overflow: ErrorKind) -> Result<char, ErrorKind> {
 		const SMALL_ASCII_A: i32 = 97;
 		const HEX_A_IN_DEC: i32 = 10;
 
-		let c_as_number = c.to_digit(ALPHABET_RADIX).unwrap() as i32;
+		let c_as_number = letters::base36_digit(c).ok_or(ErrorKind::NotALetter{value: c})?;
 		let new_number = c_as_number + num;
 		let fixed_for_char: i32 = SMALL_ASCII_A + (new_number - HEX_A_IN_DEC);
 
-		if new_number < 0 || new_number >= 36 {
-			Err(new_number)
+		if !(HEX_A_IN_DEC..36).contains(&new_number) {
+			Err(overflow)
 		}
 		else {
 			Ok(char::from_u32(fixed_for_char as u32).unwrap())
 		}
 	}
-
-	fn explain_error(e: Error) -> String {
-		match e {
-			Error::NoValue{cell: Location::Cell(_cell)} => format!("There is no value at cell {:?}", _cell),
-			Error::NoValue{cell: Location::Address(_cell)} => format!("There is no value at cell {:?}", _cell),
-			Error::NoEmployeeValue => String::from("the Employee register holds no value. Cannot add."),
-			Error::SumOfChars => String::from("cannot sum two characters!"),
-			Error::SumOverflow{character: _char, number: _num} =>
-				format!("value overflowed! {} + {} is not representable as a letter!",
-								_char, _num)
-		}
-	}
 }
 
 impl Operator for AddOp {
@@ -53,59 +37,35 @@ impl Operator for AddOp {
 		false
 	}
 
-  fn apply_to(&self,  s: &mut state::InternalState) -> Result<(), String> {
-		let memory_position = memory::extract_memory_position(self.cell, &s);
-		if let Err(error) = memory_position {
-			return Err(memory::explain(error));
-		}
+  fn apply_to(&self,  s: &mut state::InternalState) -> Result<(), ErrorKind> {
+		let memory_position = memory::extract_memory_position(self.cell, s)?;
 
-		let value_from_memory = s.memory[memory_position.unwrap()].clone();
-		let res = match value_from_memory {
-			Some(ref v) => {
-				match s.register {
-					Some(old_register) => {
-						let new_register_value: Result<Value, String> =  match (v, old_register) {
-							(&Value::Number{value: _v}, Value::Number{value: _old}) => {
-								Ok(Value::Number{value: _v + _old})
-							},
-							(&Value::Number{value: _v}, Value::Character{value: _old}) => {
-								if let Ok(new_char) = AddOp::add_number_and_char(_v, _old) {
-									Ok(Value::Character{value: new_char})
-								}
-								else {
-									Err(AddOp::explain_error(Error::SumOverflow{character: _old, number: _v}))
-								}
-							},
-							(&Value::Character{value: _v}, Value::Number{value: _old}) => {
-								if let Ok(new_char) = AddOp::add_number_and_char(_old, _v) {
-									Ok(Value::Character{value: new_char})
-								}
-								else {
-									Err(AddOp::explain_error(Error::SumOverflow{character: _v, number: _old}))
-								}
-							},
-							_ => Err(AddOp::explain_error(Error::SumOfChars))
-						};
-						
-						match new_register_value {
-							Ok(value) => {
-								s.register = Some(value);
-								Ok(())
-							},
-							Err(reason) => Err(reason)
-						}
-					}
-					_ => {
-						Err(AddOp::explain_error(Error::NoEmployeeValue))
-					}
-				}
-			}
-			_ => {
-				Err(AddOp::explain_error(Error::NoValue{cell: self.cell}))
-			}
+		let value_from_memory = match s.memory[memory_position] {
+			Some(value) => value,
+			None => return Err(ErrorKind::EmptyCell{cell: memory_position})
+		};
+		let old_register = match s.register {
+			Some(value) => value,
+			None => return Err(ErrorKind::EmptyRegister)
 		};
 
-		res
+		let extended = s.semantics == Semantics::Extended;
+		let overflow = ErrorKind::LetterOverflow{register: old_register, operand: value_from_memory};
+		let new_register_value = match (value_from_memory, old_register) {
+			(Value::Number{value: _v}, Value::Number{value: _old}) => {
+				s.checked_number(_v as i64 + _old as i64)?
+			},
+			(Value::Number{value: _v}, Value::Character{value: _old}) if extended => {
+				Value::Character{value: AddOp::add_number_and_char(_v, _old, overflow)?}
+			},
+			(Value::Character{value: _v}, Value::Number{value: _old}) if extended => {
+				Value::Character{value: AddOp::add_number_and_char(_old, _v, overflow)?}
+			},
+			_ => return Err(ErrorKind::CharArithmetic{register: old_register, operand: value_from_memory})
+		};
+
+		s.register = Some(new_register_value);
+		Ok(())
   }
 }
 
@@ -117,6 +77,8 @@ mod test {
 	use Operation;
 	use operators::Operator;
 	use operators::add::AddOp;
+	use error::ErrorKind;
+	use state::Semantics;
 
 	#[test]
 	fn add_two_numbers(){
@@ -124,7 +86,7 @@ mod test {
 			.with_memory(vec!(Some(Value::Number{value: 4})));
 		let operation = AddOp{cell: Location::Cell(0)};
 
-		let _ = operation.apply_to(&mut state).unwrap();
+		operation.apply_to(&mut state).unwrap();
 
 		assert!(match state.register {
 			Some(Value::Number{value: 9}) => true,
@@ -138,7 +100,7 @@ mod test {
 		state.memory = vec!(Some(Value::Number{value: 1}), Some(Value::Number{value: 4}));
 		let operation = AddOp{cell: Location::Address(0)};
 
-		let _ = operation.apply_to(&mut state).unwrap();
+		operation.apply_to(&mut state).unwrap();
 
 		assert!(match state.register {
 			Some(Value::Number{value: 9}) => true,
@@ -211,7 +173,9 @@ mod test {
 
 		let result = operator.apply_to(&mut state);
 
-		assert!(result.is_err());
+		assert_eq!(result, Err(ErrorKind::CharArithmetic{
+			register: Value::Character{value: 'a'},
+			operand: Value::Character{value: 'a'}}));
 	}
 
 	#[test]
@@ -254,9 +218,7 @@ mod test {
 	fn add_number_to_char(){
 		let mut state = state::InternalState::new(Some(Value::Number{value:5}), 0)
 			.with_memory(vec!(Some(Value::Character{value: 'a'})));
-		let operation = AddOp{cell: Location::Cell(0)};
-
-		let _ = state.apply(Operation::Add{cell: Location::Cell(0)}).unwrap();
+		state.apply(Operation::Add{cell: Location::Cell(0)}).unwrap();
 
 		assert!(match state.register {
 			Some(Value::Character{value: 'f'}) => true,
@@ -264,12 +226,25 @@ mod test {
 		});
 	}
 
+	#[test]
+	fn add_number_to_char_overflow_names_register(){
+		let mut state = state::InternalState::new(Some(Value::Number{value: 5}), 0)
+			.with_memory(vec!(Some(Value::Character{value: 'y'})));
+		let operation = AddOp{cell: Location::Cell(0)};
+
+		let result = operation.apply_to(&mut state);
+
+		assert_eq!(result, Err(ErrorKind::LetterOverflow{
+			register: Value::Number{value: 5},
+			operand: Value::Character{value: 'y'}}));
+	}
+
 	#[test]
 	fn add_number_to_addressed_char(){
 		let mut state = state::InternalState::new(Some(Value::Number{value: 5}), 0);
 		state.memory = vec!(Some(Value::Number{value: 1}), Some(Value::Character{value: 'a'}));
 
-		let _ = state.apply(Operation::Add{cell: Location::Address(0)}).unwrap();
+		state.apply(Operation::Add{cell: Location::Address(0)}).unwrap();
 		assert!(match state.register {
 			Some(Value::Character{value: 'f'}) => true,
 			_ => false
@@ -286,4 +261,53 @@ mod test {
 
 		assert!(result.is_err());
 	}
+
+	#[test]
+	fn add_two_numbers_overflow() {
+		let mut state = state::InternalState::new(Some(Value::Number{value: 999}), 0)
+			.with_memory(vec!(Some(Value::Number{value: 1})));
+		let operation = AddOp{cell: Location::Cell(0)};
+
+		let result = operation.apply_to(&mut state);
+
+		assert_eq!(result, Err(ErrorKind::Overflow{value: 1000}));
+		assert_eq!(state.register, Some(Value::Number{value: 999}));
+	}
+
+	#[test]
+	fn add_two_numbers_without_range() {
+		let mut state = state::InternalState::new(Some(Value::Number{value: 999}), 0)
+			.with_memory(vec!(Some(Value::Number{value: 1})))
+			.with_value_range(None);
+		let operation = AddOp{cell: Location::Cell(0)};
+
+		operation.apply_to(&mut state).unwrap();
+
+		assert_eq!(state.register, Some(Value::Number{value: 1000}));
+	}
+
+	#[test]
+	fn add_number_to_char_game_semantics() {
+		let mut state = state::InternalState::new(Some(Value::Number{value: 5}), 0)
+			.with_memory(vec!(Some(Value::Character{value: 'a'})))
+			.with_semantics(Semantics::Game);
+		let operation = AddOp{cell: Location::Cell(0)};
+
+		let result = operation.apply_to(&mut state);
+
+		assert_eq!(result, Err(ErrorKind::CharArithmetic{
+			register: Value::Number{value: 5},
+			operand: Value::Character{value: 'a'}}));
+	}
+
+	#[test]
+	fn add_number_to_symbol() {
+		let mut state = state::InternalState::new(Some(Value::Number{value: 5}), 0)
+			.with_memory(vec!(Some(Value::Character{value: '!'})));
+		let operation = AddOp{cell: Location::Cell(0)};
+
+		let result = operation.apply_to(&mut state);
+
+		assert_eq!(result, Err(ErrorKind::NotALetter{value: '!'}));
+	}
 }
@@ -2,25 +2,8 @@ use operators::Operator;
 use state::InternalState;
 use Value;
 use Location;
-
-enum Error {
-	PointerCellContainsChar,
-	NoValue{cell: Location},
-	BumpChar{value: char}
-}
-
-struct BumpOp {}
-
-impl BumpOp {
-	fn explain_error(error: Error) -> String {
-		match error {
-			Error::NoValue{cell: Location::Cell(_cell)} => format!("There is no value at cell {:?}", _cell),
-			Error::NoValue{cell: Location::Address(_cell)} => format!("There is no value at cell {:?}", _cell),
-			Error::BumpChar{value: _char} => format!("Cannot bump char {:?}", _char),
-			Error::PointerCellContainsChar => String::from("The selected cell should contain a number, not a char")
-		}
-	}
-}
+use memory;
+use error::ErrorKind;
 
 pub struct BumpPlusOp {
 	pub cell: Location
@@ -30,73 +13,34 @@ pub struct BumpMinusOp {
 	pub cell: Location
 }
 
+// both bumps store the new value in the cell and in the register
+fn bump_cell(cell: Location, s: &mut InternalState, increment: i32) -> Result<(), ErrorKind> {
+	let mempos = memory::extract_memory_position(cell, s)?;
+	match s.memory[mempos] {
+		None => Err(ErrorKind::EmptyCell{cell: mempos}),
+		Some(Value::Number{value: _num}) => {
+			s.memory[mempos] = Some(s.checked_number(_num as i64 + increment as i64)?);
+			s.register = s.memory[mempos];
+			Ok(())
+		},
+		Some(Value::Character{value: _char}) =>
+			Err(ErrorKind::BumpChar{cell: mempos, value: _char})
+	}
+}
+
 impl Operator for BumpPlusOp {
 	fn changes_instruction_counter(&self) -> bool { false }
 
-	fn apply_to(&self, s: &mut InternalState) -> Result<(), String> {
-		let memory_position = match self.cell {
-			Location::Cell(mempos) => Ok(mempos),
-			Location::Address(mempos) => {
-				let value_from_memory = s.memory[mempos].clone();
-				match value_from_memory {
-					Some(Value::Number{value: pointed_cell}) => Ok(pointed_cell as usize),
-					Some(Value::Character{value: _}) => Err(BumpOp::explain_error(Error::PointerCellContainsChar)),
-					None => Err(BumpOp::explain_error(Error::NoValue{cell: Location::Cell(mempos)}))
-				}
-			}
-		};
-		if let Err(error_message) = memory_position {
-			return Err(error_message);
-		}
-
-		let mempos = memory_position.unwrap();
-		let data = s.memory[mempos];
-		match data {
-			None => Err(BumpOp::explain_error(Error::NoValue{cell: Location::Cell(mempos)})),
-			Some(Value::Number{value: _num}) => {
-				s.memory[mempos] = Some(Value::Number{value: _num+1});
-				s.register = s.memory[mempos];
-				Ok(())
-			},
-			Some(Value::Character{value: _char}) =>
-				Err(BumpOp::explain_error(Error::BumpChar{value: _char}))
-		}
-
+	fn apply_to(&self, s: &mut InternalState) -> Result<(), ErrorKind> {
+		bump_cell(self.cell, s, 1)
 	}
 }
 
 impl Operator for BumpMinusOp {
 	fn changes_instruction_counter(&self) -> bool { false }
 
-	fn apply_to(&self, s: &mut InternalState) -> Result<(), String> {
-		let memory_position = match self.cell {
-			Location::Cell(mempos) => Ok(mempos),
-			Location::Address(mempos) => {
-				let value_from_memory = s.memory[mempos].clone();
-				match value_from_memory {
-					Some(Value::Number{value: pointed_cell}) => Ok(pointed_cell as usize),
-					Some(Value::Character{value: _}) => Err(BumpOp::explain_error(Error::PointerCellContainsChar)),
-					None => Err(BumpOp::explain_error(Error::NoValue{cell: Location::Cell(mempos)}))
-				}
-			}
-		};
-		if let Err(error_message) = memory_position {
-			return Err(error_message);
-		}
-
-		let mempos = memory_position.unwrap();
-		let data = s.memory[mempos];
-		match data {
-			None => Err(BumpOp::explain_error(Error::NoValue{cell: Location::Cell(mempos)})),
-			Some(Value::Number{value: _num}) => {
-				s.memory[mempos] = Some(Value::Number{value: _num-1});
-				s.register = s.memory[mempos];
-				Ok(())
-			},
-			Some(Value::Character{value: _char}) =>
-				Err(BumpOp::explain_error(Error::BumpChar{value: _char}))
-		}
-
+	fn apply_to(&self, s: &mut InternalState) -> Result<(), ErrorKind> {
+		bump_cell(self.cell, s, -1)
 	}
 }
 
@@ -109,6 +53,7 @@ mod test {
 	use operators::Operator;
 	use operators::bump::BumpPlusOp;
 	use operators::bump::BumpMinusOp;
+	use error::ErrorKind;
 
 	#[test]
 	fn bumpplus_number() {
@@ -136,10 +81,7 @@ mod test {
 		let result = BumpPlusOp{cell: Location::Cell(0)}.apply_to(&mut state);
 
 		assert!(result.is_err());
-		assert!(match state.register {
-			None => true,
-			_ => false
-		});
+		assert!(state.register.is_none());
 	}
 
 	#[test]
@@ -150,10 +92,7 @@ mod test {
 		let result = BumpPlusOp{cell: Location::Cell(0)}.apply_to(&mut state);
 
 		assert!(result.is_err());
-		assert!(match state.register {
-			None => true,
-			_ => false
-		});
+		assert!(state.register.is_none());
 	}
 
 	#[test]
@@ -182,10 +121,7 @@ mod test {
 		let result = BumpMinusOp{cell: Location::Cell(0)}.apply_to(&mut state);
 
 		assert!(result.is_err());
-		assert!(match state.register {
-			None => true,
-			_ => false
-		});
+		assert!(state.register.is_none());
 	}
 
 	#[test]
@@ -196,10 +132,18 @@ mod test {
 		let result = BumpMinusOp{cell: Location::Cell(0)}.apply_to(&mut state);
 
 		assert!(result.is_err());
-		assert!(match state.register {
-			None => true,
-			_ => false
-		});
+		assert!(state.register.is_none());
 	}
 
+	#[test]
+	fn bumpplus_overflow() {
+		let mut state = InternalState::new(None, 0);
+		state.memory = vec!(Some(Value::Number{value: 999}));
+
+		let result = BumpPlusOp{cell: Location::Cell(0)}.apply_to(&mut state);
+
+		assert_eq!(result, Err(ErrorKind::Overflow{value: 1000}));
+		assert_eq!(state.memory[0], Some(Value::Number{value: 999}));
+		assert!(state.register.is_none());
+	}
 }
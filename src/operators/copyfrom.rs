@@ -2,6 +2,7 @@ use operators::Operator;
 use state::InternalState;
 use Location;
 use memory;
+use error::ErrorKind;
 
 pub struct CopyFromOp {
 	pub cell: Location
@@ -9,19 +10,14 @@ pub struct CopyFromOp {
 impl Operator for CopyFromOp {
 	fn changes_instruction_counter(&self) -> bool { false }
 
-	fn apply_to(&self, s: &mut InternalState) -> Result<(), String> {
-		let memory_position = memory::extract_memory_position(self.cell, &s);
-		if let Err(error) = memory_position {
-			return Err(memory::explain(error));
-		}
-
-		let cell = memory_position.unwrap();
-		if let Some(_) = s.memory[cell] {
+	fn apply_to(&self, s: &mut InternalState) -> Result<(), ErrorKind> {
+		let cell = memory::extract_memory_position(self.cell, s)?;
+		if s.memory[cell].is_some() {
 			s.register = s.memory[cell];
 			Ok(())
 		}
 		else {
-			Err(format!("cell {} holds no value. could not copy a none value to the register", cell))
+			Err(ErrorKind::EmptyCell{cell})
 		}
 	}
 }
@@ -33,6 +29,7 @@ mod test {
 	use Location;
 	use operators::Operator;
 	use operators::copyfrom::CopyFromOp;
+	use error::ErrorKind;
 
 	#[test]
 	fn copyfrom_non_empty_cell(){
@@ -88,24 +85,58 @@ mod test {
 
 
 	#[test]
-	#[should_panic]
 	fn copyfrom_non_existent_cell() {
 		let mut state = InternalState::new(None, 0)
 			.with_memory(vec!(None));
 		let operation = CopyFromOp{cell: Location::Cell(9)};
 
-		// #[should_panic]
-		let _ = operation.apply_to(&mut state);
+		let result = operation.apply_to(&mut state);
+
+		assert_eq!(result, Err(ErrorKind::CellOutOfRange{cell: 9, floor_size: 1}));
 	}
 
 	#[test]
-	#[should_panic]
 	fn copyfrom_non_existent_addressed_cell() {
 		let mut state = InternalState::new(None, 0);
 		state.memory = vec!(Some(Value::Number{value: 9}));
 		let operation = CopyFromOp{cell: Location::Address(0)};
 
-		// #[should_panic]
-		let _ = operation.apply_to(&mut state);
+		let result = operation.apply_to(&mut state);
+
+		assert_eq!(result, Err(ErrorKind::PointerOutOfRange{cell: 0, pointer: 9, floor_size: 1}));
+	}
+
+	#[test]
+	fn copyfrom_negative_address() {
+		let mut state = InternalState::new(None, 0)
+			.with_memory(vec!(Some(Value::Number{value: -1}), Some(Value::Number{value: 5})));
+		let operation = CopyFromOp{cell: Location::Address(0)};
+
+		let result = operation.apply_to(&mut state);
+
+		assert_eq!(result, Err(ErrorKind::PointerOutOfRange{cell: 0, pointer: -1, floor_size: 2}));
+	}
+
+	#[test]
+	fn copyfrom_address_stored_out_of_floor() {
+		let mut state = InternalState::new(None, 0)
+			.with_memory(vec!(Some(Value::Number{value: 0})));
+		let operation = CopyFromOp{cell: Location::Address(500)};
+
+		let result = operation.apply_to(&mut state);
+
+		assert_eq!(result, Err(ErrorKind::CellOutOfRange{cell: 500, floor_size: 1}));
+	}
+
+	#[test]
+	fn copyfrom_larger_floor_size() {
+		let mut state = InternalState::new(None, 0)
+			.with_memory(vec!(Some(Value::Number{value: 3})))
+			.with_empty_tiles_up_to(4);
+		let operation = CopyFromOp{cell: Location::Address(0)};
+
+		let result = operation.apply_to(&mut state);
+
+		assert_eq!(result, Err(ErrorKind::EmptyCell{cell: 3}));
 	}
 }
@@ -2,6 +2,7 @@ use operators::Operator;
 use state::InternalState;
 use Location;
 use memory;
+use error::ErrorKind;
 
 pub struct CopyToOp {
 	pub cell: Location
@@ -9,21 +10,14 @@ pub struct CopyToOp {
 impl Operator for CopyToOp {
 	fn changes_instruction_counter(&self) -> bool { false }
 
-	fn apply_to(&self, s: &mut InternalState) -> Result<(), String> {
-		let memory_position = memory::extract_memory_position(self.cell, &s);
-		if let Err(error) = memory_position {
-			return Err(memory::explain(error));
-		}
-
-		let cell = memory_position.unwrap();
+	fn apply_to(&self, s: &mut InternalState) -> Result<(), ErrorKind> {
+		let cell = memory::extract_memory_position(self.cell, s)?;
 		if let Some(value) = s.register {
 			s.memory[cell] = Some(value);
 			Ok(())
 		}
 		else {
-			Err(format!("register holds no value. could not copy a None value to {}", cell))
+			Err(ErrorKind::EmptyRegister)
 		}
 	}
 }
-
-
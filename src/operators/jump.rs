@@ -1,6 +1,7 @@
 use operators::Operator;
 use state;
 use Value;
+use error::ErrorKind;
 
 #[derive(Debug, Clone, Copy)]
 pub struct LabelOp;
@@ -8,7 +9,7 @@ pub struct LabelOp;
 impl Operator for LabelOp {
     fn changes_instruction_counter(&self) -> bool { false }
 
-    fn apply_to(&self, _: &mut state::InternalState) -> Result<(), String> {
+    fn apply_to(&self, _: &mut state::InternalState) -> Result<(), ErrorKind> {
         Ok(())
     }
 }
@@ -21,7 +22,7 @@ pub struct JumpOp {
 impl Operator for JumpOp {
     fn changes_instruction_counter(&self) -> bool { true }
 
-    fn apply_to(&self, s: &mut state::InternalState) -> Result<(), String> {
+    fn apply_to(&self, s: &mut state::InternalState) -> Result<(), ErrorKind> {
         s.instruction_counter = self.next_operation;
         Ok(())
     }
@@ -35,9 +36,9 @@ pub struct JumpEqualsZeroOp {
 impl Operator for JumpEqualsZeroOp {
     fn changes_instruction_counter(&self) -> bool { true }
 
-    fn apply_to(&self, s: &mut state::InternalState) -> Result<(), String> {
+    fn apply_to(&self, s: &mut state::InternalState) -> Result<(), ErrorKind> {
         match s.register {
-            None => Err(String::from("register holds no value! cannot compare it to zero!")),
+            None => Err(ErrorKind::EmptyRegister),
             Some(Value::Number{value: 0}) => {
                 s.instruction_counter = self.next_operation;
                 Ok(())
@@ -58,10 +59,10 @@ pub struct JumpNegativeOp {
 impl Operator for JumpNegativeOp {
     fn changes_instruction_counter(&self) -> bool { true }
 
-    fn apply_to(&self, s: &mut state::InternalState) -> Result<(), String> {
+    fn apply_to(&self, s: &mut state::InternalState) -> Result<(), ErrorKind> {
         match s.register {
-            None => Err(String::from("register holds no value! cannot see if it is negative!")),
-            Some(Value::Character{value: _}) => Err(String::from("cannot compare a character to zero!")),
+            None => Err(ErrorKind::EmptyRegister),
+            Some(Value::Character{value}) => Err(ErrorKind::CharComparedToZero{value}),
             Some(Value::Number{value: _v}) if _v < 0 => {
                 s.instruction_counter = self.next_operation;
                 Ok(())
@@ -82,6 +83,7 @@ mod test {
     use operators::jump::JumpNegativeOp;
     use state::InternalState;
     use Value;
+    use error::ErrorKind;
 
     #[test]
     fn label_does_not_change_instruction_counter() {
@@ -158,4 +160,12 @@ mod test {
 
         assert!(_op.apply_to(&mut _state).is_err());
     }
+
+    #[test]
+    fn jneg_register_is_char() {
+        let mut _state = InternalState::new(Some(Value::Character{value: 'a'}), 0);
+        let _op = JumpNegativeOp{next_operation: 15};
+
+        assert_eq!(_op.apply_to(&mut _state), Err(ErrorKind::CharComparedToZero{value: 'a'}));
+    }
 }
@@ -0,0 +1,18 @@
+// how the operators turn chars into numbers
+
+// the game only knows letters: 'a' and 'A' are both 0, 'z' is 25
+pub fn alphabet_position(c: char) -> Option<i32> {
+	if c.is_ascii_alphabetic() {
+		Some(c.to_ascii_lowercase() as i32 - 'a' as i32)
+	}
+	else {
+		None
+	}
+}
+
+// extended arithmetic counts digits too: '0' is 0, 'a' is 10, 'z' is 35
+pub fn base36_digit(c: char) -> Option<i32> {
+	const ALPHABET_RADIX: u32 = 36;
+
+	c.to_digit(ALPHABET_RADIX).map(|digit| digit as i32)
+}
@@ -1,10 +1,11 @@
 use state;
+use error::ErrorKind;
 
 // define the Operator trait: every Operator
 // can modify the internal state
 pub trait Operator {
 	// rust compiler issue 35203.
-	fn apply_to(&self, /*mut*/  s: &mut state::InternalState) -> Result<(), String>;
+	fn apply_to(&self, /*mut*/  s: &mut state::InternalState) -> Result<(), ErrorKind>;
 	fn changes_instruction_counter(&self) -> bool;
 }
 
@@ -16,3 +17,4 @@ pub mod copyfrom;
 pub mod copyto;
 pub mod jump;
 pub mod bump;
+pub mod letters;
@@ -1,20 +1,53 @@
 use operators::Operator;
 use state::InternalState;
+use error::ErrorKind;
 
 pub struct OutboxOp {
 }
 impl Operator for OutboxOp {
 	fn changes_instruction_counter(&self) -> bool { false }
 
-	fn apply_to(&self, s: &mut InternalState) -> Result<(), String> {
+	fn apply_to(&self, s: &mut InternalState) -> Result<(), ErrorKind> {
 		if let Some(value) = s.register {
+			// like the game, stop at the first wrong item
+			if let Some(ref expected_output) = s.expected_output {
+				let position = s.output_tape.len();
+				let expected = expected_output.get(position).cloned();
+				if expected != Some(value) {
+					return Err(ErrorKind::WrongOutput{position, expected, actual: value});
+				}
+			}
 			s.output_tape.push(value);
 			Ok(())
 		}
 		else {
-			Err(format!("you cannot put nothing in the output queue!"))
+			Err(ErrorKind::EmptyRegister)
 		}
 	}
 }
 
 
+
+#[cfg(test)]
+mod test {
+	use Value;
+	use state::InternalState;
+	use operators::Operator;
+	use operators::outbox::OutboxOp;
+	use error::ErrorKind;
+
+	#[test]
+	fn outbox_checks_expected_output() {
+		let mut state = InternalState::new(Some(Value::Number{value: 3}), 0)
+			.with_expected_output(Some(vec!(Value::Number{value: 3}, Value::Number{value: 4})));
+
+		assert_eq!(OutboxOp{}.apply_to(&mut state), Ok(()));
+		assert_eq!(OutboxOp{}.apply_to(&mut state),
+			Err(ErrorKind::WrongOutput{position: 1, expected: Some(Value::Number{value: 4}), actual: Value::Number{value: 3}}));
+		state.register = Some(Value::Number{value: 4});
+		assert_eq!(OutboxOp{}.apply_to(&mut state), Ok(()));
+		assert_eq!(OutboxOp{}.apply_to(&mut state),
+			Err(ErrorKind::WrongOutput{position: 2, expected: None, actual: Value::Number{value: 4}}));
+		assert_eq!(state.output_tape, vec!(Value::Number{value: 3}, Value::Number{value: 4}));
+	}
+}
@@ -2,63 +2,46 @@ use operators::Operator;
 use Value;
 use Location;
 use state;
+use state::Semantics;
+use memory;
+use error::ErrorKind;
+use operators::letters;
 // --
 use std::char;
 
-enum Error {
-	NoValue{cell: Location},
-	PointerCellContainsChar,
-	NoEmployeeValue,
-	NumLessChar,
-	SubUnderflow{character: char, number: i32},
-	SubUnderflowOfChar{character: char, other_character: char}
-}
-
 pub struct SubOp {
 	pub cell: Location
 }
 
 impl SubOp {
-	fn sub_char_and_number(c: char, num: i32) -> Result<char, i32> {
-		const ALPHABET_RADIX: u32 = 36;
+	// only allowed by Semantics::Extended; overflow names the register and the operand
+	fn sub_char_and_number(c: char, num: i32, overflow: ErrorKind) -> Result<char, ErrorKind> {
 		const SMALL_ASCII_A: i32 = 97;
 		const HEX_A_IN_DEC: i32 = 10;
 
-		let c_as_number = c.to_digit(ALPHABET_RADIX).unwrap() as i32;
-		if num < c_as_number {
-			Err(num - c_as_number)
+		let c_as_number = letters::base36_digit(c).ok_or(ErrorKind::NotALetter{value: c})?;
+		let new_number = num - c_as_number;
+		let fixed_for_char: i32 = SMALL_ASCII_A - (new_number - HEX_A_IN_DEC);
+
+		// only differences up to 10 land on a letter, from 'k' down to 'a'
+		if !(0..=HEX_A_IN_DEC).contains(&new_number) {
+			Err(overflow)
 		}
 		else {
-			let new_number = num - c_as_number;
-			let fixed_for_char: u32 = (SMALL_ASCII_A - (new_number - HEX_A_IN_DEC)) as u32;
-
-			Ok(char::from_u32(fixed_for_char).unwrap())
+			Ok(char::from_u32(fixed_for_char as u32).unwrap())
 		}
 	}
 
-	fn sub_char_and_char(a: char, b: char) -> Result<i32, i32> {
-		const ALPHABET_RADIX: u32 = 36;
-
-		let a_as_number = a.to_digit(ALPHABET_RADIX).unwrap() as i32;
-		let b_as_number = b.to_digit(ALPHABET_RADIX).unwrap() as i32;
-		let new_number = b_as_number - a_as_number;
-		Ok(new_number)
-	}
+	fn sub_char_and_char(a: char, b: char, semantics: Semantics) -> Result<i32, ErrorKind> {
+		let to_number = |c: char| {
+			let number = match semantics {
+				Semantics::Game => letters::alphabet_position(c),
+				Semantics::Extended => letters::base36_digit(c)
+			};
+			number.ok_or(ErrorKind::NotALetter{value: c})
+		};
 
-	fn explain_error(e: Error) -> String {
-		match e {
-			Error::NoValue{cell: Location::Cell(_cell)} => format!("There is no value at cell {:?}", _cell),
-			Error::NoValue{cell: Location::Address(_cell)} => format!("There is no value at cell {:?}", _cell),
-			Error::PointerCellContainsChar => String::from("The selected cell should contain a number, not a char"),
-			Error::NoEmployeeValue => String::from("the Employee register holds no value. Cannot add."),
-			Error::NumLessChar => String::from("cannot perform <num> - <char>"),
-			Error::SubUnderflow{character: _char, number: _num} =>
-				format!("value underflowed! {} - {} is not representable as a letter!",
-								_char, _num),
-			Error::SubUnderflowOfChar{character: _char, other_character: _num} =>
-				format!("value underflowed! {} - {} is not representable as a letter!",
-								_char, _num)
-		}
+		Ok(to_number(b)? - to_number(a)?)
 	}
 }
 
@@ -67,70 +50,34 @@ impl Operator for SubOp {
 		false
 	}
 
-  fn apply_to(&self,  s: &mut state::InternalState) -> Result<(), String> {
-		let memory_position = match self.cell {
-			Location::Cell(mempos) => Ok(mempos),
-			Location::Address(mempos) => {
-				let value_from_memory = s.memory[mempos].clone();
-				match value_from_memory {
-					Some(Value::Number{value: pointed_cell}) => Ok(pointed_cell as usize),
-					Some(Value::Character{value: _}) => Err(SubOp::explain_error(Error::PointerCellContainsChar)),
-					None => Err(SubOp::explain_error(Error::NoValue{cell: Location::Cell(mempos)}))
-				}
-			}
+  fn apply_to(&self,  s: &mut state::InternalState) -> Result<(), ErrorKind> {
+		let memory_position = memory::extract_memory_position(self.cell, s)?;
+
+		let value_from_memory = match s.memory[memory_position] {
+			Some(value) => value,
+			None => return Err(ErrorKind::EmptyCell{cell: memory_position})
+		};
+		let old_register = match s.register {
+			Some(value) => value,
+			None => return Err(ErrorKind::EmptyRegister)
 		};
-		if let Err(error_message) = memory_position {
-			return Err(error_message);
-		}
 
-		let value_from_memory = s.memory[memory_position.unwrap()].clone();
-		let res = match value_from_memory {
-			Some(ref v) => {
-				match s.register {
-					Some(old_register) => {
-						let new_register_value: Result<Value, String> =  match (v, old_register) {
-							(&Value::Number{value: _v}, Value::Number{value: _old}) => {
-								Ok(Value::Number{value: _old - _v})
-							},
-							(&Value::Character{value: _v}, Value::Character{value: _old}) => {
-								if let Ok(new_number) = SubOp::sub_char_and_char(_v, _old) {
-									Ok(Value::Number{value: new_number})
-								}
-								else {
-									Err(SubOp::explain_error(Error::SubUnderflowOfChar{character: _old, other_character: _v}))
-								}
-							},
-							(&Value::Character{value: _v}, Value::Number{value: _old}) => {
-								if let Ok(new_char) = SubOp::sub_char_and_number(_v, _old) {
-									Ok(Value::Character{value: new_char})
-								}
-								else {
-									Err(SubOp::explain_error(Error::SubUnderflow{character: _v, number: _old}))
-								}
-							},
-							(&Value::Number{value: _v}, Value::Character{value: _old}) =>
-								Err(SubOp::explain_error(Error::NumLessChar))
-						};
-						
-						match new_register_value {
-							Ok(value) => {
-								s.register = Some(value);
-								Ok(())
-							},
-							Err(reason) => Err(reason)
-						}
-					}
-					_ => {
-						Err(SubOp::explain_error(Error::NoEmployeeValue))
-					}
-				}
-			}
-			_ => {
-				Err(SubOp::explain_error(Error::NoValue{cell: self.cell}))
-			}
+		let new_register_value = match (value_from_memory, old_register) {
+			(Value::Number{value: _v}, Value::Number{value: _old}) => {
+				s.checked_number(_old as i64 - _v as i64)?
+			},
+			(Value::Character{value: _v}, Value::Character{value: _old}) => {
+				Value::Number{value: SubOp::sub_char_and_char(_v, _old, s.semantics)?}
+			},
+			(Value::Character{value: _v}, Value::Number{value: _old}) if s.semantics == Semantics::Extended => {
+				let overflow = ErrorKind::LetterOverflow{register: old_register, operand: value_from_memory};
+				Value::Character{value: SubOp::sub_char_and_number(_v, _old, overflow)?}
+			},
+			_ => return Err(ErrorKind::CharArithmetic{register: old_register, operand: value_from_memory})
 		};
 
-		res
+		s.register = Some(new_register_value);
+		Ok(())
   }
 }
 
@@ -142,6 +89,8 @@ mod test {
 	use Operation;
 	use operators::Operator;
 	use operators::sub::SubOp;
+	use error::ErrorKind;
+	use state::Semantics;
 
 	#[test]
 	fn sub_two_numbers(){
@@ -150,7 +99,7 @@ mod test {
 			.with_memory(vec!(Some(Value::Number{value: 4})));
 		let operation = SubOp{cell: Location::Cell(0)};
 
-		let _ = operation.apply_to(&mut state).unwrap();
+		operation.apply_to(&mut state).unwrap();
 
 		assert!(match state.register {
 			Some(Value::Number{value: 1}) => true,
@@ -165,7 +114,7 @@ mod test {
 			.with_memory(vec!(Some(Value::Number{value: 5})));
 		let operation = SubOp{cell: Location::Cell(0)};
 
-		let _ = operation.apply_to(&mut state).unwrap();
+		operation.apply_to(&mut state).unwrap();
 
 		assert!(match state.register {
 			Some(Value::Number{value: -1}) => true,
@@ -179,7 +128,7 @@ mod test {
 		state.memory = vec!(Some(Value::Number{value: 1}), Some(Value::Number{value: 4}));
 		let operation = SubOp{cell: Location::Address(0)};
 
-		let _ = operation.apply_to(&mut state).unwrap();
+		operation.apply_to(&mut state).unwrap();
 
 		assert!(match state.register {
 			Some(Value::Number{value: 1}) => true,
@@ -193,7 +142,7 @@ mod test {
 		state.memory = vec!(Some(Value::Number{value: 1}), Some(Value::Number{value: 5}));
 		let operation = SubOp{cell: Location::Address(0)};
 
-		let _ = operation.apply_to(&mut state).unwrap();
+		operation.apply_to(&mut state).unwrap();
 
 		assert!(match state.register {
 			Some(Value::Number{value: -1}) => true,
@@ -357,4 +306,85 @@ mod test {
 
 		assert!(result.is_err());
 	}
+
+	#[test]
+	fn sub_char_from_large_number(){
+		let mut state = state::InternalState::new(Some(Value::Number{value: 200}), 0)
+			.with_memory(vec!(Some(Value::Character{value: 'a'})));
+		let operation = SubOp{cell: Location::Cell(0)};
+
+		let result = operation.apply_to(&mut state);
+
+		assert_eq!(result, Err(ErrorKind::LetterOverflow{
+			register: Value::Number{value: 200},
+			operand: Value::Character{value: 'a'}}));
+	}
+
+	#[test]
+	fn sub_char_from_number_past_letters(){
+		// 21 - 'a' (10) is 11, one past the difference of 10 that gives 'a'
+		let mut state = state::InternalState::new(Some(Value::Number{value: 21}), 0)
+			.with_memory(vec!(Some(Value::Character{value: 'a'})));
+		let operation = SubOp{cell: Location::Cell(0)};
+
+		assert!(match operation.apply_to(&mut state) {
+			Err(ErrorKind::LetterOverflow{..}) => true,
+			_ => false
+		});
+
+		let mut state = state::InternalState::new(Some(Value::Number{value: 20}), 0)
+			.with_memory(vec!(Some(Value::Character{value: 'a'})));
+		operation.apply_to(&mut state).unwrap();
+
+		assert_eq!(state.register, Some(Value::Character{value: 'a'}));
+	}
+
+	#[test]
+	fn sub_two_numbers_underflow() {
+		let mut state = state::InternalState::new(Some(Value::Number{value: -999}), 0)
+			.with_memory(vec!(Some(Value::Number{value: 1})));
+		let operation = SubOp{cell: Location::Cell(0)};
+
+		let result = operation.apply_to(&mut state);
+
+		assert_eq!(result, Err(ErrorKind::Overflow{value: -1000}));
+	}
+
+	#[test]
+	fn sub_char_to_char_game_semantics() {
+		let mut state = state::InternalState::new(Some(Value::Character{value: 'B'}), 0)
+			.with_memory(vec!(Some(Value::Character{value: 'a'})))
+			.with_semantics(Semantics::Game);
+		let operation = SubOp{cell: Location::Cell(0)};
+
+		operation.apply_to(&mut state).unwrap();
+
+		assert_eq!(state.register, Some(Value::Number{value: 1}));
+	}
+
+	#[test]
+	fn sub_digit_char_game_semantics() {
+		let mut state = state::InternalState::new(Some(Value::Character{value: '5'}), 0)
+			.with_memory(vec!(Some(Value::Character{value: 'a'})))
+			.with_semantics(Semantics::Game);
+		let operation = SubOp{cell: Location::Cell(0)};
+
+		let result = operation.apply_to(&mut state);
+
+		assert_eq!(result, Err(ErrorKind::NotALetter{value: '5'}));
+	}
+
+	#[test]
+	fn sub_number_to_char_game_semantics() {
+		let mut state = state::InternalState::new(Some(Value::Number{value: 15}), 0)
+			.with_memory(vec!(Some(Value::Character{value: 'a'})))
+			.with_semantics(Semantics::Game);
+		let operation = SubOp{cell: Location::Cell(0)};
+
+		let result = operation.apply_to(&mut state);
+
+		assert_eq!(result, Err(ErrorKind::CharArithmetic{
+			register: Value::Number{value: 15},
+			operand: Value::Character{value: 'a'}}));
+	}
 }
@@ -0,0 +1,188 @@
+use std::fmt;
+
+use Operation;
+use CodeIterator;
+use RunOutcome;
+use Step;
+use level::Targets;
+use limits::Limits;
+use state::InternalState;
+
+// size and speed challenges, graded like the game does: size counts the
+// instructions of the program, speed averages the executed ones over some inputs.
+
+// labels are not instructions in the game
+pub fn program_size(operations: &[Operation]) -> usize {
+    operations.iter().filter(|operation| **operation != Operation::Label).count()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoredRun {
+    // executed instructions, labels excluded like in the game
+    pub steps: u32,
+    pub outcome: RunOutcome
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoreReport {
+    pub size: usize,
+    pub runs: Vec<ScoredRun>,
+    pub targets: Targets
+}
+
+impl ScoreReport {
+    pub fn all_completed(&self) -> bool {
+        self.runs.iter().all(|run| run.outcome.is_completed())
+    }
+
+    pub fn average_steps(&self) -> Option<f64> {
+        if self.runs.is_empty() {
+            return None;
+        }
+        let total: u64 = self.runs.iter().map(|run| run.steps as u64).sum();
+        Some(total as f64 / self.runs.len() as f64)
+    }
+
+    // None when the level has no size target
+    pub fn meets_size(&self) -> Option<bool> {
+        self.targets.size.map(|target| self.size <= target)
+    }
+
+    // failed runs miss the target whatever their steps
+    pub fn meets_speed(&self) -> Option<bool> {
+        self.targets.speed.map(|target| {
+            self.all_completed() && self.average_steps().is_some_and(|average| average <= target as f64)
+        })
+    }
+}
+
+fn describe_target<T: fmt::Display>(target: Option<T>, met: Option<bool>) -> String {
+    match (target, met) {
+        (Some(target), Some(true)) => format!(" (target {}, met)", target),
+        (Some(target), _) => format!(" (target {}, missed)", target),
+        _ => String::new()
+    }
+}
+
+impl fmt::Display for ScoreReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "size: {}{}", self.size, describe_target(self.targets.size, self.meets_size()))?;
+        match self.average_steps() {
+            Some(average) => writeln!(f, "speed: {:.2} average steps over {} inputs{}",
+                average, self.runs.len(), describe_target(self.targets.speed, self.meets_speed()))?,
+            None => writeln!(f, "speed: no inputs")?
+        }
+        for (index, run) in self.runs.iter().enumerate() {
+            match run.outcome {
+                RunOutcome::RuntimeError(ref error) | RunOutcome::LimitExceeded(ref error) =>
+                    writeln!(f, "input {}: {} steps, {}", index + 1, run.steps, error)?,
+                _ => writeln!(f, "input {}: {} steps", index + 1, run.steps)?
+            }
+        }
+        Ok(())
+    }
+}
+
+// runs the program counting the steps like the game does: the interpreter counts
+// jumping over a label as an executed instruction (see executed_instructions),
+// the game does not, so the labels are left out here
+pub fn scored_run(operations: &[Operation], state: &mut InternalState, limits: Limits) -> ScoredRun {
+    let mut iterator = CodeIterator::without_dump(state, operations.to_vec())
+        .with_limits(limits);
+
+    let mut steps = 0;
+    loop {
+        let counter = iterator.state.instruction_counter;
+        let is_label = operations.get(counter) == Some(&Operation::Label);
+        match iterator.next() {
+            Some(Ok(Step::Executed(_))) => if !is_label { steps += 1; },
+            _ => break
+        }
+    }
+    // the iterator already stopped, this only tells why
+    ScoredRun{steps, outcome: iterator.run()}
+}
+
+// runs the program from every state
+pub fn score(operations: &[Operation], states: Vec<InternalState>, targets: Targets, limits: Limits) -> ScoreReport {
+    let runs = states.into_iter()
+        .map(|mut state| scored_run(operations, &mut state, limits))
+        .collect();
+
+    ScoreReport{size: program_size(operations), runs, targets}
+}
+
+#[cfg(test)]
+mod test {
+    use Value;
+    use Location;
+    use Operation;
+    use RunOutcome;
+    use level::Targets;
+    use limits::Limits;
+    use state::InternalState;
+    use score::{program_size, score, scored_run};
+
+    fn program() -> Vec<Operation> {
+        vec!(
+            Operation::Label,
+            Operation::Inbox,
+            Operation::CopyTo{cell: Location::Cell(0)},
+            Operation::Outbox,
+            Operation::Jump{next_operation: 0}
+        )
+    }
+
+    fn state(inbox: Vec<Value>) -> InternalState {
+        InternalState::new(None, 0)
+            .with_input_tape(inbox)
+            .with_memory(vec!(None))
+    }
+
+    #[test]
+    fn size_ignores_labels() {
+        assert_eq!(program_size(&program()), 4);
+        assert_eq!(program_size(&[]), 0);
+    }
+
+    #[test]
+    fn average_over_inputs() {
+        let states = vec!(
+            state(vec!(Value::Number{value: 1})),
+            state(vec!(Value::Number{value: 1}, Value::Number{value: 2}, Value::Number{value: 3}))
+        );
+        let report = score(&program(), states, Targets{size: Some(4), speed: Some(5)}, Limits::default());
+
+        assert_eq!(report.runs.iter().map(|run| run.steps).collect::<Vec<_>>(), vec!(4, 12));
+        assert_eq!(report.average_steps(), Some(8.0));
+        assert_eq!(report.meets_size(), Some(true));
+        assert_eq!(report.meets_speed(), Some(false));
+        assert_eq!(report.to_string(), "size: 4 (target 4, met)\n\
+            speed: 8.00 average steps over 2 inputs (target 5, missed)\n\
+            input 1: 4 steps\n\
+            input 2: 12 steps\n");
+    }
+
+    #[test]
+    fn steps_leave_labels_out() {
+        let mut state = state(vec!(Value::Number{value: 1}, Value::Number{value: 2}));
+        let run = scored_run(&program(), &mut state, Limits::default());
+
+        // the label ran three times: twice before an item, once before the empty inbox
+        assert_eq!(run.steps, 8);
+        assert_eq!(state.executed_instructions(), 11);
+    }
+
+    #[test]
+    fn failed_runs_miss_the_speed_target() {
+        let states = vec!(InternalState::new(None, 0).with_input_tape(vec!(Value::Number{value: 1})));
+        let report = score(&program(), states, Targets{size: None, speed: Some(100)}, Limits::default());
+
+        assert!(match report.runs[0].outcome {
+            RunOutcome::RuntimeError(_) => true,
+            _ => false
+        });
+        assert_eq!(report.meets_size(), None);
+        assert_eq!(report.meets_speed(), Some(false));
+    }
+}
@@ -0,0 +1,262 @@
+use Operation;
+use Program;
+use json::{JsonOperation, JsonOperand, to_program, read_contents, merge_load_errors};
+use error::{LoadError, LoadErrorKind};
+
+// Parser for the textual assembly used by Human Resource Machine itself,
+// the one you get by copying a program from the game:
+//
+//   -- HUMAN RESOURCE MACHINE PROGRAM --
+//
+//   a:
+//       INBOX
+//       COPYTO   0
+//       ADD      [4]
+//       JUMPZ    a
+//
+//   DEFINE COMMENT 0
+//   eJzzYWBgEGTQ...;
+//
+// Every line is translated to the same JsonOperation hrm-compiler would emit,
+// so labels are resolved exactly like json-ified source code.
+
+pub fn to_mnemonic(instruction: &str) -> Option<&'static str> {
+    match instruction.to_uppercase().as_str() {
+        "INBOX" => Some("inbox"),
+        "OUTBOX" => Some("outbox"),
+        "COPYFROM" => Some("copyfrom"),
+        "COPYTO" => Some("copyto"),
+        "ADD" => Some("add"),
+        "SUB" => Some("sub"),
+        "BUMPUP" => Some("bump+"),
+        "BUMPDN" => Some("bump-"),
+        "JUMP" => Some("jmp"),
+        "JUMPZ" => Some("jez"),
+        "JUMPN" => Some("jneg"),
+        _ => None
+    }
+}
+
+fn to_operand(mnemonic: &str, operand: &str) -> Result<JsonOperand, LoadErrorKind> {
+    if mnemonic.starts_with('j') {
+        return Ok(JsonOperand::Label(String::from(operand)));
+    }
+
+    let invalid_operand = || LoadErrorKind::InvalidOperand{
+        operation: String::from(mnemonic),
+        operand: String::from(operand)
+    };
+    if operand.starts_with('[') && operand.ends_with(']') {
+        operand[1..operand.len() - 1].trim().parse::<u32>()
+            .map(JsonOperand::Address)
+            .map_err(|_| invalid_operand())
+    }
+    else {
+        operand.parse::<u32>()
+            .map(JsonOperand::Cell)
+            .map_err(|_| invalid_operand())
+    }
+}
+
+fn parse_instruction(line: &str) -> Result<Option<JsonOperation>, LoadErrorKind> {
+    let mut tokens = line.split_whitespace();
+    let instruction = match tokens.next() {
+        Some(instruction) => instruction,
+        None => return Ok(None)
+    };
+
+    // comments placed on the floor or in the code are only graphics.
+    if instruction.to_uppercase() == "COMMENT" {
+        return Ok(None);
+    }
+
+    let mnemonic = to_mnemonic(instruction)
+        .ok_or_else(|| LoadErrorKind::UnknownOperation{operation: String::from(instruction)})?;
+    let operand = match tokens.next() {
+        Some(operand) => Some(to_operand(mnemonic, operand)?),
+        None => None
+    };
+    if let Some(unexpected) = tokens.next() {
+        return Err(LoadErrorKind::InvalidOperand{
+            operation: String::from(mnemonic),
+            operand: String::from(unexpected)
+        });
+    }
+
+    Ok(Some(JsonOperation::new(String::from(mnemonic), operand)))
+}
+
+struct ParsedSource {
+    // None for the lines that could not be parsed
+    operations: Vec<Option<JsonOperation>>,
+    positions: Vec<(usize, usize)>,
+    errors: Vec<LoadError>
+}
+
+impl ParsedSource {
+    fn push(&mut self, operation: Option<JsonOperation>, line: usize, column: usize) {
+        self.operations.push(operation);
+        self.positions.push((line, column));
+    }
+}
+
+fn column_of(raw_line: &str, text: &str) -> usize {
+    let offset = text.as_ptr() as usize - raw_line.as_ptr() as usize;
+    raw_line[..offset].chars().count() + 1
+}
+
+fn to_json_operations(source: &str) -> ParsedSource {
+    let mut parsed = ParsedSource{operations: vec!(), positions: vec!(), errors: vec!()};
+    let mut in_define = false;
+
+    for (line_index, raw_line) in source.lines().enumerate() {
+        let line_number = line_index + 1;
+        let line = raw_line.trim();
+
+        // DEFINE blocks hold the drawings of comments and labelled tiles,
+        // encoded over several lines and terminated by a ';'.
+        if in_define {
+            in_define = !line.ends_with(';');
+            continue;
+        }
+        if line.is_empty() || line.starts_with("--") {
+            continue;
+        }
+        if line.to_uppercase().starts_with("DEFINE") {
+            in_define = !line.ends_with(';');
+            continue;
+        }
+
+        let mut instruction = line;
+        if let Some(colon) = line.find(':') {
+            let label_name = line[..colon].trim();
+            parsed.push(
+                Some(JsonOperation::new(String::from("label"), Some(JsonOperand::Label(String::from(label_name))))),
+                line_number, column_of(raw_line, line));
+            instruction = line[colon + 1..].trim();
+        }
+
+        let column = column_of(raw_line, instruction);
+        match parse_instruction(instruction) {
+            Ok(Some(operation)) => parsed.push(Some(operation), line_number, column),
+            Ok(None) => (),
+            Err(kind) => {
+                let error = LoadError::new(kind)
+                    .at_instruction(parsed.operations.len())
+                    .at_position(line_number, column);
+                parsed.errors.push(error);
+                parsed.push(None, line_number, column);
+            }
+        }
+    }
+
+    parsed
+}
+
+pub fn read_file(srcpath: String) -> Result<Vec<Operation>, Vec<LoadError>> {
+    read_program_file(srcpath).map(|program| program.operations)
+}
+
+pub fn read_instructions(source: String) -> Result<Vec<Operation>, Vec<LoadError>> {
+    read_program(source).map(|program| program.operations)
+}
+
+pub fn read_program_file(srcpath: String) -> Result<Program, Vec<LoadError>> {
+    let contents = read_contents(&srcpath).map_err(|error| vec!(error))?;
+    read_program(contents)
+}
+
+// like json::read_program, every problem in the source is reported.
+pub fn read_program(source: String) -> Result<Program, Vec<LoadError>> {
+    let parsed = to_json_operations(&source);
+    let errors = parsed.errors;
+
+    to_program(parsed.operations, &parsed.positions)
+        .map_err(|more_errors| merge_load_errors(errors, more_errors))
+}
+
+#[cfg(test)]
+mod test {
+    use Operation;
+    use Location;
+    use source::read_instructions;
+    use error::{LoadError, LoadErrorKind};
+
+    #[test]
+    fn read_game_program() {
+        let source = String::from("-- HUMAN RESOURCE MACHINE PROGRAM --\n\
+            \n\
+            a:\n    \
+                INBOX   \n    \
+                COPYTO   0\n    \
+                ADD      [4]\n    \
+                BUMPUP   3\n    \
+                BUMPDN   [3]\n    \
+                OUTBOX  \n    \
+                JUMP     a\n");
+
+        let result = read_instructions(source).unwrap();
+
+        assert_eq!(result, vec!(
+            Operation::Label,
+            Operation::Inbox,
+            Operation::CopyTo{cell: Location::Cell(0)},
+            Operation::Add{cell: Location::Address(4)},
+            Operation::BumpPlus{cell: Location::Cell(3)},
+            Operation::BumpMinus{cell: Location::Address(3)},
+            Operation::Outbox,
+            Operation::Jump{next_operation: 0}
+        ));
+    }
+
+    #[test]
+    fn read_forward_jumps() {
+        let source = String::from("INBOX\nJUMPZ b\nJUMPN c\nb:\nc:\nOUTBOX\n");
+
+        let result = read_instructions(source).unwrap();
+
+        assert_eq!(result[1], Operation::JumpEqualsZero{next_operation: 3});
+        assert_eq!(result[2], Operation::JumpNegative{next_operation: 4});
+    }
+
+    #[test]
+    fn comments_and_defines_are_skipped() {
+        let source = String::from("INBOX\n\
+            COMMENT  0\n\
+            OUTBOX\n\
+            \n\
+            DEFINE COMMENT 0\n\
+            eJzzYWBgEGTQ\n\
+            AAAAAA;\n\
+            DEFINE LABEL 3\n\
+            eJwzZGBgMGRgYGRiYGBk;\n");
+
+        let result = read_instructions(source).unwrap();
+
+        assert_eq!(result, vec!(Operation::Inbox, Operation::Outbox));
+    }
+
+    #[test]
+    fn unknown_instruction() {
+        let errors = read_instructions(String::from("INBOX\n  TELEPORT 3\n")).unwrap_err();
+
+        assert_eq!(errors, vec!(
+            LoadError::new(LoadErrorKind::UnknownOperation{operation: String::from("TELEPORT")})
+                .at_instruction(1)
+                .at_position(2, 3)
+        ));
+    }
+
+    #[test]
+    fn every_error_is_reported() {
+        let errors = read_instructions(String::from("COPYTO [x]\nJUMP nowhere\nADD 1 2\n")).unwrap_err();
+
+        assert_eq!(errors.len(), 3);
+        assert_eq!(errors[0].kind, LoadErrorKind::InvalidOperand{
+            operation: String::from("copyto"),
+            operand: String::from("[x]")
+        });
+        assert_eq!(errors[1].kind, LoadErrorKind::UnknownLabel{label: String::from("nowhere")});
+        assert_eq!(errors[2].line, Some(3));
+    }
+}
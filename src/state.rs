@@ -2,20 +2,56 @@ use Value;
 use Operation;
 use operators;
 use operators::Operator;
+use error::{RuntimeError, ErrorKind};
 
-#[derive(Serialize, Debug, Clone)]
+// numbers an employee can hold: the game rejects anything else
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct ValueRange {
+	pub min: i32,
+	pub max: i32
+}
+
+impl ValueRange {
+	pub fn game() -> Self {
+		ValueRange{min: -999, max: 999}
+	}
+
+	pub fn contains(&self, value: i32) -> bool {
+		self.min <= value && value <= self.max
+	}
+}
+
+// how strictly operators follow the game
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum Semantics {
+	// only what Human Resource Machine allows: letters can only be
+	// subtracted from letters, and any other char is rejected.
+	Game,
+	// numbers can also be added to and subtracted from letters
+	Extended
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
 pub struct InternalState {
   pub register: Option<Value>,
 	pub input_tape: Vec<Value>,
 	pub output_tape: Vec<Value>,
 	pub memory: Vec<Option<Value>>,
 	pub instruction_counter: usize,
-	_executed_instructions: u32
+	_executed_instructions: u32,
+	// None lets numbers grow as much as an i32 allows
+	#[serde(skip)]
+	pub value_range: Option<ValueRange>,
+	#[serde(skip)]
+	pub semantics: Semantics,
+	// when known, outbox stops the program at the first wrong item
+	#[serde(skip)]
+	pub expected_output: Option<Vec<Value>>
 }
 
 
 macro_rules! apply_operation {
-	($self: ident, $operator:expr) => ({
+	($self: ident, $operation: ident, $operator:expr) => ({
 		let op = $operator;
     match op.apply_to($self) {
 			Ok(()) => {
@@ -25,8 +61,8 @@ macro_rules! apply_operation {
 				$self.increase_executed_instructions();
 				Ok(())
 			},
-			Err(reason) => {
-				Err(reason)
+			Err(kind) => {
+				Err(RuntimeError::new($self.instruction_counter, $operation, kind))
 			}
 		}
 	})
@@ -40,7 +76,10 @@ impl InternalState {
 			output_tape: vec!(),
 			memory: vec!(),
 			instruction_counter: counter,
-			_executed_instructions: 0
+			_executed_instructions: 0,
+			value_range: Some(ValueRange::game()),
+			semantics: Semantics::Extended,
+			expected_output: None
 		}
 	}
 
@@ -54,6 +93,50 @@ impl InternalState {
 		self
 	}
 
+	// adds empty tiles after the memory until it has that many; the floor
+	// is the memory, there is no size kept apart from it.
+	pub fn with_empty_tiles_up_to(mut self, tiles: usize) -> Self {
+		if self.memory.len() < tiles {
+			self.memory.resize(tiles, None);
+		}
+		self
+	}
+
+	// the number of tiles, which is the length of the memory
+	pub fn floor_size(&self) -> usize {
+		self.memory.len()
+	}
+
+	pub fn with_value_range(mut self, value_range: Option<ValueRange>) -> Self {
+		self.value_range = value_range;
+		self
+	}
+
+	pub fn with_semantics(mut self, semantics: Semantics) -> Self {
+		self.semantics = semantics;
+		self
+	}
+
+	pub fn with_expected_output(mut self, expected_output: Option<Vec<Value>>) -> Self {
+		self.expected_output = expected_output;
+		self
+	}
+
+	// operators producing new numbers check them here before storing them
+	pub fn checked_number(&self, number: i64) -> Result<Value, ErrorKind> {
+		let fits = match self.value_range {
+			Some(range) => number >= range.min as i64 && number <= range.max as i64,
+			None => number >= i32::MIN as i64 && number <= i32::MAX as i64
+		};
+
+		if fits {
+			Ok(Value::Number{value: number as i32})
+		}
+		else {
+			Err(ErrorKind::Overflow{value: number})
+		}
+	}
+
     pub fn executed_instructions(&self) -> u32 {
 		self._executed_instructions
 	}
@@ -62,43 +145,47 @@ impl InternalState {
 		self._executed_instructions += 1;
 	}
 
-	pub fn apply(&mut self, op: Operation) -> Result<(), String> {
+	pub fn decrease_executed_instructions(&mut self) {
+		self._executed_instructions -= 1;
+	}
+
+	pub fn apply(&mut self, op: Operation) -> Result<(), RuntimeError> {
 		match op {
 			Operation::Add{cell: _cell} => {
-				apply_operation!(self, operators::add::AddOp{cell: _cell})
+				apply_operation!(self, op, operators::add::AddOp{cell: _cell})
 			},
 			Operation::Sub{cell: _cell} => {
-				apply_operation!(self, operators::sub::SubOp{cell: _cell})
+				apply_operation!(self, op, operators::sub::SubOp{cell: _cell})
 			},
 			Operation::Inbox => {
-				apply_operation!(self, operators::inbox::InboxOp{})
+				apply_operation!(self, op, operators::inbox::InboxOp{})
 			},
 			Operation::Outbox => {
-				apply_operation!(self, operators::outbox::OutboxOp{})
+				apply_operation!(self, op, operators::outbox::OutboxOp{})
 			},
 			Operation::CopyFrom{cell: _cell} => {
-				apply_operation!(self, operators::copyfrom::CopyFromOp{cell: _cell})
+				apply_operation!(self, op, operators::copyfrom::CopyFromOp{cell: _cell})
 			},
 			Operation::CopyTo{cell: _cell} => {
-				apply_operation!(self, operators::copyto::CopyToOp{cell: _cell})
+				apply_operation!(self, op, operators::copyto::CopyToOp{cell: _cell})
 			},
 			Operation::Label => {
-				apply_operation!(self, operators::jump::LabelOp)
+				apply_operation!(self, op, operators::jump::LabelOp)
 			},
 			Operation::Jump{next_operation: _next_op} => {
-				apply_operation!(self, operators::jump::JumpOp{next_operation: _next_op})
+				apply_operation!(self, op, operators::jump::JumpOp{next_operation: _next_op})
 			},
 			Operation::JumpEqualsZero{next_operation: _next_op} => {
-				apply_operation!(self, operators::jump::JumpEqualsZeroOp{next_operation: _next_op})
+				apply_operation!(self, op, operators::jump::JumpEqualsZeroOp{next_operation: _next_op})
 			}
 			Operation::JumpNegative{next_operation: _next_op} => {
-				apply_operation!(self, operators::jump::JumpNegativeOp{next_operation: _next_op})
+				apply_operation!(self, op, operators::jump::JumpNegativeOp{next_operation: _next_op})
 			}
 			Operation::BumpPlus{cell: _cell} => {
-				apply_operation!(self, operators::bump::BumpPlusOp{cell: _cell})
+				apply_operation!(self, op, operators::bump::BumpPlusOp{cell: _cell})
 			}
 			Operation::BumpMinus{cell: _cell} => {
-				apply_operation!(self, operators::bump::BumpMinusOp{cell: _cell})
+				apply_operation!(self, op, operators::bump::BumpMinusOp{cell: _cell})
 			}
 		}
 	}
@@ -108,7 +195,9 @@ impl InternalState {
 mod test {
 	use state::InternalState;
 	use Value;
+	use Location;
 	use Operation;
+	use error::{RuntimeError, ErrorKind};
 
 	#[test]
 	fn executed_counter_at_start() {
@@ -126,4 +215,35 @@ mod test {
 
 		assert_eq!(state.executed_instructions(), 1);
 	}
-}
\ No newline at end of file
+
+	#[test]
+	fn checked_number_in_game_range() {
+		let state = InternalState::new(None, 0);
+
+		assert_eq!(state.checked_number(-999), Ok(Value::Number{value: -999}));
+		assert_eq!(state.checked_number(1000), Err(ErrorKind::Overflow{value: 1000}));
+	}
+
+	#[test]
+	fn checked_number_without_range() {
+		let state = InternalState::new(None, 0)
+			.with_value_range(None);
+
+		assert_eq!(state.checked_number(1000), Ok(Value::Number{value: 1000}));
+		assert_eq!(state.checked_number(i32::MAX as i64 + 1), Err(ErrorKind::Overflow{value: i32::MAX as i64 + 1}));
+	}
+
+	#[test]
+	fn failing_operation_reports_instruction() {
+		let mut state = InternalState::new(None, 4)
+			.with_memory(vec!(None, Some(Value::Character{value: 'a'})));
+
+		let result = state.apply(Operation::CopyFrom{cell: Location::Address(1)});
+
+		assert_eq!(result, Err(RuntimeError::new(
+			4,
+			Operation::CopyFrom{cell: Location::Address(1)},
+			ErrorKind::PointerCellContainsChar{cell: 1, value: 'a'})));
+		assert_eq!(state.instruction_counter, 4);
+	}
+}
@@ -0,0 +1,331 @@
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use serde_json;
+
+use Operation;
+use RunOutcome;
+use json::{read_config, read_manifest};
+use level::{judge, Verdict};
+use limits::Limits;
+use score::scored_run;
+use state::InternalState;
+use error::{LoadError, LoadErrorKind};
+
+// runs one program on many inputs, each one optionally listing its expected_output
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestCase {
+    pub name: String,
+    pub state: InternalState
+}
+
+// a case of a manifest; the input path is relative to the manifest
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManifestEntry {
+    pub name: Option<String>,
+    pub input: String
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseResult {
+    pub name: String,
+    pub verdict: Verdict,
+    // executed instructions, labels excluded
+    pub steps: u32,
+    pub outcome: RunOutcome
+}
+
+impl CaseResult {
+    // failures are wrong programs, errors are programs that could not finish
+    pub fn status(&self) -> &'static str {
+        match self.verdict {
+            Verdict::Pass => "pass",
+            Verdict::RuntimeError(_) | Verdict::LimitExceeded(_) => "error",
+            Verdict::ForbiddenInstruction{..} | Verdict::WrongOutbox{..} => "fail"
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestReport {
+    pub results: Vec<CaseResult>
+}
+
+// the file that could not be loaded, with the reason
+pub type CaseLoadError = (String, LoadError);
+
+fn load_case(name: String, path: &Path) -> Result<TestCase, CaseLoadError> {
+    let path = path.to_string_lossy().into_owned();
+    read_config(path.clone())
+        .map(|state| TestCase{name, state})
+        .map_err(|error| (path, error))
+}
+
+// every .json file of the directory is an input, named after the file
+pub fn cases_from_directory(directory: &str) -> Result<Vec<TestCase>, CaseLoadError> {
+    let entries = fs::read_dir(directory)
+        .map_err(|reason| (String::from(directory), LoadError::new(LoadErrorKind::Io{reason: reason.to_string()})))?;
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+        .collect();
+    paths.sort();
+
+    paths.iter()
+        .map(|path| {
+            let name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+            load_case(name, path)
+        })
+        .collect()
+}
+
+// cases without a name are named after their input
+pub fn cases_from_manifest(manifest: &str) -> Result<Vec<TestCase>, CaseLoadError> {
+    let entries = read_manifest(manifest).map_err(|error| (String::from(manifest), error))?;
+    let directory = Path::new(manifest).parent().unwrap_or_else(|| Path::new(""));
+
+    entries.into_iter()
+        .map(|entry| {
+            let path = directory.join(&entry.input);
+            load_case(entry.name.unwrap_or(entry.input), &path)
+        })
+        .collect()
+}
+
+fn run_case(operations: &[Operation], case: TestCase, limits: Limits) -> CaseResult {
+    let mut state = case.state;
+    let run = scored_run(operations, &mut state, limits);
+    let verdict = judge(&run.outcome, &state.output_tape, state.expected_output.as_deref());
+
+    CaseResult{name: case.name, verdict, steps: run.steps, outcome: run.outcome}
+}
+
+// results keep the order of the cases, whatever thread ran them
+pub fn run_tests(operations: &[Operation], cases: Vec<TestCase>, limits: Limits, threads: usize) -> TestReport {
+    let next_case = AtomicUsize::new(0);
+    let workers = threads.max(1).min(cases.len().max(1));
+
+    let mut results: Vec<(usize, CaseResult)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| scope.spawn(|| {
+                let mut results = vec!();
+                loop {
+                    let index = next_case.fetch_add(1, Ordering::SeqCst);
+                    match cases.get(index) {
+                        Some(case) => results.push((index, run_case(operations, case.clone(), limits))),
+                        None => return results
+                    }
+                }
+            }))
+            .collect();
+        handles.into_iter()
+            .flat_map(|handle| handle.join().expect("a test thread panicked"))
+            .collect()
+    });
+    results.sort_by_key(|pair| pair.0);
+
+    TestReport{results: results.into_iter().map(|pair| pair.1).collect()}
+}
+
+fn describe_problem(result: &CaseResult) -> String {
+    match result.verdict {
+        Verdict::Pass => String::new(),
+        ref verdict => verdict.to_string().trim_start_matches("fail: ").to_string()
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[derive(Serialize)]
+struct JsonCaseResult<'a> {
+    name: &'a str,
+    status: &'static str,
+    steps: u32,
+    problem: Option<String>
+}
+
+#[derive(Serialize)]
+struct JsonTestReport<'a> {
+    total: usize,
+    passed: usize,
+    cases: Vec<JsonCaseResult<'a>>
+}
+
+impl TestReport {
+    pub fn passed(&self) -> usize {
+        self.results.iter().filter(|result| result.verdict.is_pass()).count()
+    }
+
+    pub fn all_passed(&self) -> bool {
+        self.passed() == self.results.len()
+    }
+
+    pub fn to_table(&self) -> String {
+        let rows: Vec<[String; 4]> = self.results.iter()
+            .map(|result| [result.name.clone(), result.status().to_string(), result.steps.to_string(), describe_problem(result)])
+            .collect();
+        let header = [String::from("case"), String::from("result"), String::from("steps"), String::from("problem")];
+        let mut widths = [0; 3];
+        for row in rows.iter().chain(Some(&header)) {
+            for column in 0..3 {
+                widths[column] = widths[column].max(row[column].chars().count());
+            }
+        }
+
+        let mut table = String::new();
+        for row in Some(&header).into_iter().chain(rows.iter()) {
+            let line = format!("{:w0$}  {:w1$}  {:>w2$}  {}",
+                row[0], row[1], row[2], row[3], w0 = widths[0], w1 = widths[1], w2 = widths[2]);
+            table.push_str(line.trim_end());
+            table.push('\n');
+        }
+        table.push_str(&format!("{} of {} cases passed\n", self.passed(), self.results.len()));
+        table
+    }
+
+    pub fn to_junit(&self, suite_name: &str) -> String {
+        let count = |status| self.results.iter().filter(|result| result.status() == status).count();
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!("<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\">\n",
+            escape_xml(suite_name), self.results.len(), count("fail"), count("error")));
+        for result in &self.results {
+            let name = escape_xml(&result.name);
+            match result.status() {
+                "pass" => xml.push_str(&format!("  <testcase name=\"{}\" classname=\"{}\"/>\n", name, escape_xml(suite_name))),
+                status => {
+                    let tag = if status == "fail" { "failure" } else { "error" };
+                    xml.push_str(&format!("  <testcase name=\"{}\" classname=\"{}\">\n", name, escape_xml(suite_name)));
+                    xml.push_str(&format!("    <{} message=\"{}\"/>\n", tag, escape_xml(&describe_problem(result))));
+                    xml.push_str("  </testcase>\n");
+                }
+            }
+        }
+        xml.push_str("</testsuite>\n");
+        xml
+    }
+
+    pub fn to_json(&self) -> String {
+        let report = JsonTestReport {
+            total: self.results.len(),
+            passed: self.passed(),
+            cases: self.results.iter()
+                .map(|result| JsonCaseResult {
+                    name: &result.name,
+                    status: result.status(),
+                    steps: result.steps,
+                    problem: if result.verdict.is_pass() { None } else { Some(describe_problem(result)) }
+                })
+                .collect()
+        };
+        serde_json::to_string(&report).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use Value;
+    use Operation;
+    use limits::Limits;
+    use state::InternalState;
+    use suite::{TestCase, run_tests, cases_from_directory, cases_from_manifest};
+    use std::env;
+    use std::fs;
+    use std::process;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn program() -> Vec<Operation> {
+        vec!(Operation::Label, Operation::Inbox, Operation::Outbox, Operation::Jump{next_operation: 0})
+    }
+
+    // the inbox is given in the order the items come in
+    fn case(name: &str, inbox: Vec<i32>, expected: Option<Vec<i32>>) -> TestCase {
+        let to_values = |numbers: Vec<i32>| numbers.into_iter().map(|value| Value::Number{value}).collect::<Vec<_>>();
+        let mut input_tape = to_values(inbox);
+        input_tape.reverse();
+        TestCase {
+            name: String::from(name),
+            state: InternalState::new(None, 0)
+                .with_input_tape(input_tape)
+                .with_expected_output(expected.map(to_values))
+        }
+    }
+
+    fn cases() -> Vec<TestCase> {
+        vec!(
+            case("copies", vec!(1, 2), Some(vec!(1, 2))),
+            case("wrong", vec!(1, 2), Some(vec!(1, 3))),
+            case("unchecked", vec!(5), None),
+            case("missing", vec!(4), Some(vec!(4, 4)))
+        )
+    }
+
+    #[test]
+    fn runs_cases_in_threads() {
+        let report = run_tests(&program(), cases(), Limits::default(), 3);
+
+        let statuses: Vec<_> = report.results.iter().map(|result| (result.name.as_str(), result.status(), result.steps)).collect();
+        assert_eq!(statuses, vec!(("copies", "pass", 6), ("wrong", "fail", 4), ("unchecked", "pass", 3), ("missing", "fail", 3)));
+        assert_eq!(report.passed(), 2);
+        assert!(!report.all_passed());
+    }
+
+    #[test]
+    fn table() {
+        let report = run_tests(&program(), cases(), Limits::default(), 1);
+
+        assert_eq!(report.to_table(), "\
+case       result  steps  problem
+copies     pass        6
+wrong      fail        4  outbox item 1 should be 3, got 2
+unchecked  pass        3
+missing    fail        3  outbox item 1 should be 4, got nothing
+2 of 4 cases passed
+");
+    }
+
+    #[test]
+    fn junit_and_json() {
+        let report = run_tests(&program(), vec!(cases().remove(0), cases().remove(1)), Limits::default(), 2);
+
+        assert_eq!(report.to_junit("copy<>"), "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<testsuite name=\"copy&lt;&gt;\" tests=\"2\" failures=\"1\" errors=\"0\">
+  <testcase name=\"copies\" classname=\"copy&lt;&gt;\"/>
+  <testcase name=\"wrong\" classname=\"copy&lt;&gt;\">
+    <failure message=\"outbox item 1 should be 3, got 2\"/>
+  </testcase>
+</testsuite>
+");
+        assert_eq!(report.to_json(), "{\"total\":2,\"passed\":1,\"cases\":[\
+            {\"name\":\"copies\",\"status\":\"pass\",\"steps\":6,\"problem\":null},\
+            {\"name\":\"wrong\",\"status\":\"fail\",\"steps\":4,\"problem\":\"outbox item 1 should be 3, got 2\"}]}");
+    }
+
+    #[test]
+    fn loads_directories_and_manifests() {
+        // a directory of its own, so that runs at the same time do not share files
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().subsec_nanos();
+        let directory = env::temp_dir().join(format!("hrm_suite_{}_{}", process::id(), nanos));
+        fs::create_dir_all(directory.join("inputs")).unwrap();
+        fs::write(directory.join("inputs/b.json"), "{\"input_tape\": [1], \"memory\": []}").unwrap();
+        fs::write(directory.join("inputs/a.json"), "{\"input_tape\": [2], \"memory\": [], \"expected_output\": [2]}").unwrap();
+        fs::write(directory.join("inputs/notes.txt"), "not a case").unwrap();
+        fs::write(directory.join("manifest.json"), "[{\"name\": \"first\", \"input\": \"inputs/b.json\"}, {\"input\": \"inputs/a.json\"}]").unwrap();
+
+        let from_directory = cases_from_directory(&directory.join("inputs").to_string_lossy()).unwrap();
+        let from_manifest = cases_from_manifest(&directory.join("manifest.json").to_string_lossy()).unwrap();
+
+        assert_eq!(from_directory.iter().map(|case| case.name.as_str()).collect::<Vec<_>>(), vec!("a", "b"));
+        assert_eq!(from_directory[0].state.expected_output, Some(vec!(Value::Number{value: 2})));
+        assert_eq!(from_manifest.iter().map(|case| case.name.as_str()).collect::<Vec<_>>(), vec!("first", "inputs/a.json"));
+        assert!(cases_from_manifest(&directory.join("missing.json").to_string_lossy()).is_err());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
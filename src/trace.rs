@@ -0,0 +1,288 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::sync::mpsc::Sender;
+
+use json::serialize_state;
+use state::InternalState;
+use error::RuntimeError;
+
+// where CodeIterator reports the states it goes through, and how often
+
+// when CodeIterator reports a state
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Moment {
+    // an instruction ran
+    Step,
+    // the program stopped because of an error
+    Error,
+    // the program ended, running past the last instruction or emptying the inbox
+    End
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Granularity {
+    EveryStep,
+    OnError,
+    // the state the program stopped in, with or without errors
+    Final,
+    // every N executed instructions and the final state
+    EveryNSteps(u32)
+}
+
+impl Granularity {
+    pub fn wants(&self, moment: Moment, s: &InternalState) -> bool {
+        match (*self, moment) {
+            (Granularity::EveryStep, _) => true,
+            (Granularity::OnError, moment) => moment == Moment::Error,
+            (Granularity::Final, moment) => moment != Moment::Step,
+            (Granularity::EveryNSteps(steps), Moment::Step) =>
+                steps != 0 && s.executed_instructions().is_multiple_of(steps),
+            (Granularity::EveryNSteps(_), _) => true
+        }
+    }
+}
+
+pub trait TraceSink {
+    fn record(&mut self, s: &InternalState, error: Option<&RuntimeError>) -> io::Result<()>;
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// lets callers keep the sink and look at it once the program ran
+impl<T: TraceSink + ?Sized> TraceSink for &mut T {
+    fn record(&mut self, s: &InternalState, error: Option<&RuntimeError>) -> io::Result<()> {
+        (**self).record(s, error)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (**self).flush()
+    }
+}
+
+// one json object per line, as hrm-proxy expects
+pub struct JsonLines<W: Write> {
+    writer: W
+}
+
+impl<W: Write> JsonLines<W> {
+    pub fn new(writer: W) -> Self {
+        JsonLines{writer}
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl JsonLines<BufWriter<File>> {
+    // new states are added to the ones already in the file
+    pub fn append_to(path: &str) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .append(true).create(true).truncate(false)
+            .open(path)?;
+        Ok(JsonLines::new(BufWriter::new(file)))
+    }
+}
+
+impl JsonLines<io::Stdout> {
+    pub fn stdout() -> Self {
+        JsonLines::new(io::stdout())
+    }
+}
+
+impl<W: Write> TraceSink for JsonLines<W> {
+    fn record(&mut self, s: &InternalState, error: Option<&RuntimeError>) -> io::Result<()> {
+        self.writer.write_all(serialize_state(s, error).as_bytes())?;
+        self.writer.write_all(b"\n")
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+pub struct NullSink;
+
+impl TraceSink for NullSink {
+    fn record(&mut self, _: &InternalState, _: Option<&RuntimeError>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceRecord {
+    pub state: InternalState,
+    pub error: Option<RuntimeError>
+}
+
+impl TraceRecord {
+    fn new(s: &InternalState, error: Option<&RuntimeError>) -> Self {
+        TraceRecord{state: s.clone(), error: error.cloned()}
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VecSink {
+    pub records: Vec<TraceRecord>
+}
+
+impl VecSink {
+    pub fn new() -> Self {
+        VecSink{records: vec!()}
+    }
+}
+
+impl TraceSink for VecSink {
+    fn record(&mut self, s: &InternalState, error: Option<&RuntimeError>) -> io::Result<()> {
+        self.records.push(TraceRecord::new(s, error));
+        Ok(())
+    }
+}
+
+pub struct ChannelSink {
+    sender: Sender<TraceRecord>
+}
+
+impl ChannelSink {
+    pub fn new(sender: Sender<TraceRecord>) -> Self {
+        ChannelSink{sender}
+    }
+}
+
+impl TraceSink for ChannelSink {
+    fn record(&mut self, s: &InternalState, error: Option<&RuntimeError>) -> io::Result<()> {
+        self.sender.send(TraceRecord::new(s, error))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "the trace receiver is gone"))
+    }
+}
+
+// a sink with its granularity; after the first failure nothing else is
+// written and the error is kept for finish.
+pub struct Tracer<'a> {
+    sink: Box<dyn TraceSink + 'a>,
+    granularity: Granularity,
+    error: Option<io::Error>
+}
+
+impl<'a> Tracer<'a> {
+    pub fn new(sink: Box<dyn TraceSink + 'a>, granularity: Granularity) -> Self {
+        Tracer{sink, granularity, error: None}
+    }
+
+    pub fn disabled() -> Self {
+        Tracer::new(Box::new(NullSink), Granularity::Final)
+    }
+
+    // a tracer whose sink could not be created
+    pub fn failed(error: io::Error) -> Self {
+        Tracer{error: Some(error), ..Tracer::disabled()}
+    }
+
+    pub fn report(&mut self, moment: Moment, s: &InternalState, error: Option<&RuntimeError>) {
+        if self.error.is_some() || !self.granularity.wants(moment, s) {
+            return;
+        }
+
+        let mut result = self.sink.record(s, error);
+        if moment != Moment::Step {
+            result = result.and_then(|_| self.sink.flush());
+        }
+        if let Err(reason) = result {
+            self.error = Some(reason);
+        }
+    }
+
+    // flushes the sink, returning the first error met while tracing
+    pub fn finish(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(reason) => Err(reason),
+            None => self.sink.flush()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use Value;
+    use Operation;
+    use CodeIterator;
+    use state::InternalState;
+    use trace::{Granularity, Moment, JsonLines, VecSink, ChannelSink, Tracer};
+    use std::sync::mpsc::channel;
+
+    fn program() -> Vec<Operation> {
+        vec!(
+            Operation::Inbox,
+            Operation::Outbox,
+            Operation::Jump{next_operation: 0}
+        )
+    }
+
+    fn state() -> InternalState {
+        InternalState::new(None, 0)
+            .with_input_tape(vec!(Value::Number{value: 1}, Value::Number{value: 2}))
+    }
+
+    #[test]
+    fn granularity() {
+        let mut s = state();
+        s.increase_executed_instructions();
+
+        assert!(Granularity::EveryStep.wants(Moment::Step, &s));
+        assert!(!Granularity::OnError.wants(Moment::End, &s));
+        assert!(Granularity::Final.wants(Moment::Error, &s));
+        assert!(!Granularity::Final.wants(Moment::Step, &s));
+        assert!(!Granularity::EveryNSteps(2).wants(Moment::Step, &s));
+        s.increase_executed_instructions();
+        assert!(Granularity::EveryNSteps(2).wants(Moment::Step, &s));
+    }
+
+    #[test]
+    fn json_lines() {
+        let mut sink = JsonLines::new(vec!());
+        {
+            let mut s = state();
+            let iterator = CodeIterator::without_dump(&mut s, program())
+                .with_trace(Tracer::new(Box::new(&mut sink), Granularity::Final));
+            for _ in iterator {}
+        }
+
+        let written = String::from_utf8(sink.into_inner()).unwrap();
+        assert_eq!(written.lines().count(), 1);
+        assert!(written.starts_with("{\"internal_state\":"));
+        assert!(written.contains("\"ended_with_error\":true"));
+    }
+
+    #[test]
+    fn every_step_in_a_vec() {
+        let mut sink = VecSink::new();
+        {
+            let mut s = state();
+            let mut iterator = CodeIterator::without_dump(&mut s, program())
+                .with_trace(Tracer::new(Box::new(&mut sink), Granularity::EveryNSteps(3)));
+            for _ in iterator.by_ref() {}
+            assert!(iterator.finish_trace().is_ok());
+        }
+
+        let executed: Vec<u32> = sink.records.iter().map(|record| record.state.executed_instructions()).collect();
+        assert_eq!(executed, vec!(3, 6, 6));
+        assert!(sink.records[2].error.is_some());
+    }
+
+    #[test]
+    fn closed_channel_is_reported() {
+        let (sender, receiver) = channel();
+        let mut s = state();
+        let mut iterator = CodeIterator::without_dump(&mut s, program())
+            .with_trace(Tracer::new(Box::new(ChannelSink::new(sender)), Granularity::EveryStep));
+
+        assert!(iterator.next().is_some());
+        assert_eq!(receiver.recv().map(|record| record.state.output_tape.len()), Ok(0));
+        drop(receiver);
+        for _ in iterator.by_ref() {}
+
+        assert!(iterator.finish_trace().is_err());
+    }
+}